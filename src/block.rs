@@ -1,21 +1,64 @@
 use {
     super::{
-        hashable::Hashable,
+        blockchain::BlockValidationErr,
+        encoding::{encode_list, Decodable, DecodeErr, Decoder, Encodable, ENCODING_VERSION},
+        hashable::{HashAlgorithm, Hashable},
+        merkle::{merkle_root, MerkleProof},
+        miner::{Miner, MiningErr, MiningStats},
+        target::{self, U256},
         transaction::Transaction,
-        types::Hash,
-        utility::{difficulty_bytes_as_u128, u128_bytes, u32_bytes, u64_bytes},
+        types::Hash256,
+        utility::{u128_bytes, u32_bytes, u64_bytes},
     },
     std::fmt::{self, Debug, Formatter},
 };
 
-pub struct Block {
+pub const MAX_BLOCK_SIZE: usize = 1_000_000;
+
+#[derive(Clone)]
+pub struct BlockHeader {
     pub index: u32,
     pub timestamp: u128,
-    pub hash: Hash,
-    pub prev_block_hash: Hash,
+    pub prev_block_hash: Hash256,
+    pub merkle_root: Hash256,
     pub nonce: u64,
+    pub bits: u32,
+}
+
+impl Encodable for BlockHeader {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend(&u32_bytes(&self.index));
+        bytes.extend(&u128_bytes(&self.timestamp));
+        self.prev_block_hash.encode(bytes);
+        self.merkle_root.encode(bytes);
+        bytes.extend(&u64_bytes(&self.nonce));
+        bytes.extend(&u32_bytes(&self.bits));
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        Ok(BlockHeader {
+            index: decoder.u32()?,
+            timestamp: decoder.u128()?,
+            prev_block_hash: Hash256::decode(decoder)?,
+            merkle_root: Hash256::decode(decoder)?,
+            nonce: decoder.u64()?,
+            bits: decoder.u32()?,
+        })
+    }
+}
+
+impl Hashable for BlockHeader {
+    fn bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+pub struct Block {
+    pub header: BlockHeader,
+    pub hash: Hash256,
     pub transactions: Vec<Transaction>,
-    pub difficulty: u128,
 }
 
 impl Debug for Block {
@@ -23,11 +66,11 @@ impl Debug for Block {
         write!(
             f,
             "Block[{}]: {} at: {} with: {} nonce: {}",
-            &self.index,
-            &hex::encode(&self.hash),
-            &self.timestamp,
+            &self.header.index,
+            &self.hash,
+            &self.header.timestamp,
             &self.transactions.len(),
-            &self.nonce,
+            &self.header.nonce,
         )
     }
 }
@@ -36,53 +79,151 @@ impl Block {
     pub fn new(
         index: u32,
         timestamp: u128,
-        prev_block_hash: Hash,
+        prev_block_hash: Hash256,
         transactions: Vec<Transaction>,
-        difficulty: u128,
+        bits: u32,
     ) -> Self {
-        Block {
-            index,
-            timestamp,
-            hash: vec![0; 32],
-            prev_block_hash,
-            nonce: 0,
+        let mut block = Block {
+            header: BlockHeader {
+                index,
+                timestamp,
+                prev_block_hash,
+                merkle_root: Hash256::ZERO,
+                nonce: 0,
+                bits,
+            },
+            hash: Hash256::ZERO,
             transactions,
-            difficulty,
-        }
+        };
+        block.header.merkle_root = block.merkle_root();
+
+        block
+    }
+
+    pub fn txids(&self) -> Vec<Hash256> {
+        self.transactions
+            .iter()
+            .map(|transaction| transaction.hash())
+            .collect()
+    }
+
+    pub fn merkle_root(&self) -> Hash256 {
+        merkle_root(&self.txids())
+    }
+
+    pub fn merkle_proof(&self, txid: &Hash256) -> Option<MerkleProof> {
+        let txids = self.txids();
+        let index = txids.iter().position(|id| id == txid)?;
+
+        MerkleProof::new(&txids, index)
+    }
+
+    pub fn check_sha256(&self) -> Result<(), BlockValidationErr> {
+        self.check_with(HashAlgorithm::Sha256, HashAlgorithm::Sha256)
     }
 
-    pub fn mine(&mut self) {
-        for nonce_attempt in 0..(u64::max_value()) {
-            self.nonce = nonce_attempt;
-            let hash = self.hash();
-            if check_difficulty(&hash, self.difficulty) {
-                self.hash = hash;
-                return;
+    pub fn check_with(
+        &self,
+        block_hash: HashAlgorithm,
+        pow_hash: HashAlgorithm,
+    ) -> Result<(), BlockValidationErr> {
+        let hash = self.header.hash_with(block_hash);
+        if self.hash != hash {
+            return Err(BlockValidationErr::InvalidHash {
+                expected: hash,
+                actual: self.hash,
+            });
+        }
+
+        let pow = if pow_hash == block_hash {
+            hash
+        } else {
+            self.header.hash_with(pow_hash)
+        };
+        if !check_difficulty(&pow, self.header.bits) {
+            return Err(BlockValidationErr::InsufficientProofOfWork {
+                hash: pow,
+                bits: self.header.bits,
+            });
+        }
+
+        let merkle_root = self.merkle_root();
+        if self.header.merkle_root != merkle_root {
+            return Err(BlockValidationErr::InvalidMerkleRoot {
+                expected: merkle_root,
+                actual: self.header.merkle_root,
+            });
+        }
+
+        let size = self.to_bytes().len();
+        if MAX_BLOCK_SIZE < size {
+            return Err(BlockValidationErr::OversizedBlock {
+                maximum: MAX_BLOCK_SIZE,
+                actual: size,
+            });
+        }
+
+        if let Some((coinbase, transactions)) = self.transactions.split_first() {
+            if !coinbase.is_coinbase() {
+                return Err(BlockValidationErr::InvalidCoinbaseTransaction {
+                    txid: coinbase.hash(),
+                });
+            } else if coinbase.height != self.header.index {
+                return Err(BlockValidationErr::InvalidCoinbaseTransactionHeight {
+                    expected: self.header.index,
+                    actual: coinbase.height,
+                });
+            } else if let Some(tx) = transactions
+                .iter()
+                .position(|transaction| transaction.is_coinbase())
+            {
+                return Err(BlockValidationErr::MisplacedCoinbaseTransaction { tx: tx + 1 });
             }
         }
+
+        if let Some(tx) = self
+            .transactions
+            .iter()
+            .position(|transaction| transaction.output_value().is_none())
+        {
+            return Err(BlockValidationErr::ValueOutOfRange { tx });
+        }
+
+        Ok(())
+    }
+
+    pub fn mine_sha256(&mut self) -> Result<MiningStats, MiningErr> {
+        Miner::default().mine(self)
     }
 }
 
-impl Hashable for Block {
-    fn bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
+impl Encodable for Block {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.push(ENCODING_VERSION);
+        self.header.encode(bytes);
+        self.hash.encode(bytes);
+        encode_list(bytes, &self.transactions);
+    }
+}
 
-        bytes.extend(&u32_bytes(&self.index));
-        bytes.extend(&u128_bytes(&self.timestamp));
-        bytes.extend(&self.prev_block_hash);
-        bytes.extend(&u64_bytes(&self.nonce));
-        bytes.extend(
-            self.transactions
-                .iter()
-                .flat_map(|transaction| transaction.bytes())
-                .collect::<Vec<u8>>(),
-        );
-        bytes.extend(&u128_bytes(&self.difficulty));
+impl Decodable for Block {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        decoder.version()?;
 
-        bytes
+        Ok(Block {
+            header: BlockHeader::decode(decoder)?,
+            hash: Hash256::decode(decoder)?,
+            transactions: decoder.list()?,
+        })
+    }
+}
+
+impl Hashable for Block {
+    fn bytes(&self) -> Vec<u8> {
+        self.header.bytes()
     }
 }
 
-pub fn check_difficulty(hash: &Hash, difficulty: u128) -> bool {
-    difficulty_bytes_as_u128(&hash) < difficulty
+pub fn check_difficulty(hash: &Hash256, bits: u32) -> bool {
+    target::target(bits).is_some_and(|target| U256::from(hash) <= target)
 }
@@ -1,100 +1,985 @@
 use {
     super::{
-        block::{check_difficulty, Block},
-        types::Hash,
+        block::Block,
+        clock::{Clock, SystemClock},
+        merkle::MerkleProof,
+        params::Params,
+        store::BlockStore,
+        target::{self, U256},
+        transaction::{money_range, OutPoint, Output, Transaction, MAX_MONEY},
+        types::Hash256,
+        wallet::{address, verify_signature},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fmt::{self, Display, Formatter},
+        io,
+        path::Path,
+        sync::Arc,
+        thread,
     },
-    std::collections::HashSet,
 };
 
 #[derive(Debug, PartialEq)]
 pub enum BlockValidationErr {
-    AchronologicalTimestamp,
-    InsufficientInputValue,
-    InvalidCoinbaseTransaction,
-    InvalidCoinbaseTransactionFee,
-    InvalidGenesisBlockFormat,
-    InvalidHash,
-    InvalidInput,
-    MismatchedIndex,
-    MismatchedPreviousHash,
+    DuplicateBlock {
+        hash: Hash256,
+    },
+    ExcessiveCoinbaseValue {
+        maximum: u64,
+        actual: u64,
+    },
+    InsufficientInputValue {
+        tx: usize,
+        input_value: u64,
+        output_value: u64,
+    },
+    InsufficientProofOfWork {
+        hash: Hash256,
+        bits: u32,
+    },
+    InvalidCoinbaseTransaction {
+        txid: Hash256,
+    },
+    InvalidCoinbaseTransactionFee {
+        minimum: u64,
+        actual: u64,
+    },
+    InvalidCoinbaseTransactionHeight {
+        expected: u32,
+        actual: u32,
+    },
+    InvalidDifficulty {
+        expected: u32,
+        actual: u32,
+    },
+    InvalidGenesisBlockFormat {
+        prev_block_hash: Hash256,
+    },
+    InvalidHash {
+        expected: Hash256,
+        actual: Hash256,
+    },
+    InvalidInput {
+        tx: usize,
+        input: usize,
+        outpoint: OutPoint,
+    },
+    InvalidMerkleRoot {
+        expected: Hash256,
+        actual: Hash256,
+    },
+    InvalidSignature {
+        tx: usize,
+        input: usize,
+    },
+    InvalidTransactionHeight {
+        tx: usize,
+        actual: u32,
+    },
+    MismatchedIndex {
+        expected: u32,
+        actual: u32,
+    },
+    MismatchedPreviousHash {
+        prev_block_hash: Hash256,
+    },
+    MisplacedCoinbaseTransaction {
+        tx: usize,
+    },
+    OversizedBlock {
+        maximum: usize,
+        actual: usize,
+    },
+    PrematureCoinbaseSpend {
+        tx: usize,
+        input: usize,
+        mature_height: u32,
+        height: u32,
+    },
+    StorageFailure(io::ErrorKind),
+    TimestampTooEarly {
+        median_time_past: u128,
+        actual: u128,
+    },
+    TimestampTooLate {
+        maximum: u128,
+        actual: u128,
+    },
+    ValueOutOfRange {
+        tx: usize,
+    },
+}
+
+impl Display for BlockValidationErr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use BlockValidationErr::*;
+
+        match self {
+            DuplicateBlock { hash } => write!(f, "block {} is already known", hash),
+            ExcessiveCoinbaseValue { maximum, actual } => write!(
+                f,
+                "coinbase pays {} which exceeds subsidy plus fees of {}",
+                actual, maximum
+            ),
+            InsufficientInputValue {
+                tx,
+                input_value,
+                output_value,
+            } => write!(
+                f,
+                "transaction {} spends {} but only has {} in inputs",
+                tx, output_value, input_value
+            ),
+            InsufficientProofOfWork { hash, bits } => write!(
+                f,
+                "block hash {} does not meet target bits {:#010x}",
+                hash, bits
+            ),
+            InvalidCoinbaseTransaction { txid } => write!(
+                f,
+                "first transaction {} is not a coinbase",
+                txid
+            ),
+            InvalidCoinbaseTransactionFee { minimum, actual } => write!(
+                f,
+                "coinbase pays {} which is less than the fees of {}",
+                actual, minimum
+            ),
+            InvalidCoinbaseTransactionHeight { expected, actual } => write!(
+                f,
+                "coinbase height is {} but the block height is {}",
+                actual, expected
+            ),
+            InvalidDifficulty { expected, actual } => write!(
+                f,
+                "target bits are {:#010x} but should be {:#010x}",
+                actual, expected
+            ),
+            InvalidGenesisBlockFormat { prev_block_hash } => write!(
+                f,
+                "genesis block has non-zero previous hash {}",
+                prev_block_hash
+            ),
+            InvalidHash { expected, actual } => write!(
+                f,
+                "block hash is {} but the header hashes to {}",
+                actual,
+                expected
+            ),
+            InvalidInput {
+                tx,
+                input,
+                outpoint,
+            } => write!(
+                f,
+                "transaction {} input {} spends missing or already spent output {}:{}",
+                tx,
+                input,
+                outpoint.txid,
+                outpoint.index
+            ),
+            InvalidMerkleRoot { expected, actual } => write!(
+                f,
+                "merkle root is {} but the transactions hash to {}",
+                actual,
+                expected
+            ),
+            InvalidSignature { tx, input } => write!(
+                f,
+                "transaction {} input {} has an invalid key or signature",
+                tx, input
+            ),
+            InvalidTransactionHeight { tx, actual } => write!(
+                f,
+                "transaction {} has height {} but only a coinbase may set one",
+                tx, actual
+            ),
+            MismatchedIndex { expected, actual } => {
+                write!(f, "block index is {} but should be {}", actual, expected)
+            }
+            MismatchedPreviousHash { prev_block_hash } => write!(
+                f,
+                "previous block {} is unknown",
+                prev_block_hash
+            ),
+            MisplacedCoinbaseTransaction { tx } => {
+                write!(f, "transaction {} is a coinbase but is not first", tx)
+            }
+            OversizedBlock { maximum, actual } => write!(
+                f,
+                "block is {} bytes which exceeds the maximum of {}",
+                actual, maximum
+            ),
+            PrematureCoinbaseSpend {
+                tx,
+                input,
+                mature_height,
+                height,
+            } => write!(
+                f,
+                "transaction {} input {} spends a coinbase output at height {} before its maturity at {}",
+                tx, input, height, mature_height
+            ),
+            StorageFailure(kind) => write!(f, "failed to store block: {}", kind),
+            TimestampTooEarly {
+                median_time_past,
+                actual,
+            } => write!(
+                f,
+                "timestamp {} is not after the median time past of {}",
+                actual, median_time_past
+            ),
+            TimestampTooLate { maximum, actual } => write!(
+                f,
+                "timestamp {} is later than the maximum of {}",
+                actual, maximum
+            ),
+            ValueOutOfRange { tx } => write!(
+                f,
+                "transaction {} has a value outside the range 0..={}",
+                tx, MAX_MONEY
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlockValidationErr {}
+
+#[derive(Debug, PartialEq)]
+pub enum ChainEvent {
+    BlockConnected(Hash256),
+    BlockDisconnected(Hash256),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnspentOutput {
+    pub output: Output,
+    pub height: u32,
+    pub coinbase: bool,
+}
+
+impl UnspentOutput {
+    pub fn mature_height(&self, coinbase_maturity: u32) -> u32 {
+        if self.coinbase {
+            self.height
+                .saturating_add(coinbase_maturity)
+                .saturating_add(1)
+        } else {
+            self.height
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BlockUndo {
+    pub spent: Vec<(OutPoint, UnspentOutput)>,
+    pub created: Vec<OutPoint>,
+}
+
+impl BlockUndo {
+    fn revert(&self, unspent_outputs: &mut HashMap<OutPoint, UnspentOutput>) {
+        for outpoint in &self.created {
+            unspent_outputs.remove(outpoint);
+        }
+        unspent_outputs.extend(self.spent.iter().cloned());
+    }
 }
 
 pub struct Blockchain {
     pub blocks: Vec<Block>,
-    unspent_outputs: HashSet<Hash>,
+    undo: Vec<BlockUndo>,
+    side_blocks: HashMap<Hash256, Block>,
+    chain_work: HashMap<Hash256, U256>,
+    params: Params,
+    unspent_outputs: HashMap<OutPoint, UnspentOutput>,
+    store: Option<BlockStore>,
+    validation_threads: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Blockchain::new()
+    }
 }
 
 impl Blockchain {
     pub fn new() -> Self {
+        Blockchain::with_params(Params::default())
+    }
+
+    pub fn with_params(params: Params) -> Self {
         Blockchain {
             blocks: vec![],
-            unspent_outputs: HashSet::new(),
-        }
-    }
-
-    pub fn update_with_block(&mut self, block: Block) -> Result<(), BlockValidationErr> {
-        let i = self.blocks.len();
-        if i as u32 != block.index {
-            return Err(BlockValidationErr::MismatchedIndex);
-        } else if !check_difficulty(&block.hash, block.difficulty) {
-            return Err(BlockValidationErr::InvalidHash);
-        } else if 0 == i && vec![0; 32] != block.prev_block_hash {
-            return Err(BlockValidationErr::InvalidGenesisBlockFormat);
-        } else if 0 < i {
-            let prev_block = &self.blocks[i - 1];
-            if block.timestamp < prev_block.timestamp {
-                return Err(BlockValidationErr::AchronologicalTimestamp);
-            } else if block.prev_block_hash != prev_block.hash {
-                return Err(BlockValidationErr::MismatchedPreviousHash);
+            undo: vec![],
+            side_blocks: HashMap::new(),
+            chain_work: HashMap::new(),
+            params,
+            unspent_outputs: HashMap::new(),
+            store: None,
+            validation_threads: 0,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Blockchain::open_with_params(path, Params::default())
+    }
+
+    pub fn open_with_params<P: AsRef<Path>>(path: P, params: Params) -> io::Result<Self> {
+        let mut store = BlockStore::open(path)?;
+        let mut blockchain = Blockchain::with_params(params);
+        let mut tip = store.tip();
+
+        for offset in store.offsets() {
+            if let Some((_, hash)) = tip.filter(|&(tip_offset, _)| tip_offset < offset) {
+                blockchain.restore_tip(&hash)?;
+                tip = None;
+            }
+
+            let block = store.read_at(offset)?;
+            let hash = block.hash;
+            let result = blockchain
+                .check_block(&block, None)
+                .and_then(|()| blockchain.connect_block(block));
+            match result {
+                Ok(_) | Err(BlockValidationErr::DuplicateBlock { .. }) => {}
+                Err(err) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("stored block {} is invalid: {}", hash, err),
+                    ))
+                }
+            }
+        }
+        if let Some((_, hash)) = tip {
+            blockchain.restore_tip(&hash)?;
+        }
+        blockchain.store = Some(store);
+
+        Ok(blockchain)
+    }
+
+    fn restore_tip(&mut self, hash: &Hash256) -> io::Result<()> {
+        let Some(block) = self.block(hash) else {
+            return Ok(());
+        };
+
+        if !self.is_active(block) {
+            return self.reorganize(*hash).map(|_| ()).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("stored tip {} is invalid: {}", hash, err),
+                )
+            });
+        }
+
+        while self.blocks.last().is_some_and(|tip| &tip.hash != hash) {
+            let block = self.rewind_tip();
+            self.side_blocks.insert(block.hash, block);
+        }
+
+        Ok(())
+    }
+
+    pub fn validation_threads(&self) -> usize {
+        match self.validation_threads {
+            0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
+
+    pub fn set_validation_threads(&mut self, threads: usize) {
+        self.validation_threads = threads;
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Arc::new(clock);
+    }
+
+    pub fn now(&self) -> u128 {
+        self.clock.now()
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn block(&self, hash: &Hash256) -> Option<&Block> {
+        self.side_blocks
+            .get(hash)
+            .or_else(|| self.blocks.iter().rev().find(|block| &block.hash == hash))
+    }
+
+    pub fn merkle_proof(&self, txid: &Hash256) -> Option<(u32, MerkleProof)> {
+        self.blocks.iter().rev().find_map(|block| {
+            block
+                .merkle_proof(txid)
+                .map(|proof| (block.header.index, proof))
+        })
+    }
+
+    pub fn chain_work(&self) -> U256 {
+        self.blocks
+            .last()
+            .map_or(U256::ZERO, |tip| self.chain_work[&tip.hash])
+    }
+
+    pub fn next_bits(&self) -> u32 {
+        match self.blocks.last() {
+            Some(tip) => self.bits_after(tip),
+            None => self.params.initial_bits,
+        }
+    }
+
+    pub fn difficulty(&self) -> f64 {
+        target::difficulty(self.next_bits(), self.params.initial_bits)
+    }
+
+    pub fn median_time_past(&self) -> u128 {
+        self.blocks
+            .last()
+            .map_or(0, |tip| self.median_time_past_after(tip))
+    }
+
+    pub fn block_undo(&self, index: u32) -> Option<&BlockUndo> {
+        self.undo.get(index as usize)
+    }
+
+    pub fn unspent_output(&self, outpoint: &OutPoint) -> Option<&UnspentOutput> {
+        self.unspent_outputs.get(outpoint)
+    }
+
+    pub fn unspent_outputs(&self) -> &HashMap<OutPoint, UnspentOutput> {
+        &self.unspent_outputs
+    }
+
+    pub fn total_supply(&self) -> u64 {
+        self.unspent_outputs
+            .values()
+            .fold(0, |total: u64, unspent| {
+                total.saturating_add(unspent.output.value)
+            })
+    }
+
+    pub fn validate_block(&self, block: &Block) -> Result<(), BlockValidationErr> {
+        let maximum = self.now().saturating_add(self.params.max_future_drift);
+
+        self.check_block(block, Some(maximum))
+    }
+
+    fn check_block(
+        &self,
+        block: &Block,
+        max_timestamp: Option<u128>,
+    ) -> Result<(), BlockValidationErr> {
+        if self.chain_work.contains_key(&block.hash) {
+            return Err(BlockValidationErr::DuplicateBlock { hash: block.hash });
+        }
+
+        block.check_with(self.params.block_hash, self.params.pow_hash)?;
+
+        if let Some(maximum) = max_timestamp.filter(|&maximum| maximum < block.header.timestamp) {
+            return Err(BlockValidationErr::TimestampTooLate {
+                maximum,
+                actual: block.header.timestamp,
+            });
+        }
+
+        if self.blocks.is_empty() {
+            if 0 != block.header.index {
+                return Err(BlockValidationErr::MismatchedIndex {
+                    expected: 0,
+                    actual: block.header.index,
+                });
+            } else if block.header.bits != self.params.initial_bits {
+                return Err(BlockValidationErr::InvalidDifficulty {
+                    expected: self.params.initial_bits,
+                    actual: block.header.bits,
+                });
+            } else if !block.header.prev_block_hash.is_zero() {
+                return Err(BlockValidationErr::InvalidGenesisBlockFormat {
+                    prev_block_hash: block.header.prev_block_hash,
+                });
+            }
+        } else {
+            let Some(prev_block) = self.block(&block.header.prev_block_hash) else {
+                return Err(BlockValidationErr::MismatchedPreviousHash {
+                    prev_block_hash: block.header.prev_block_hash,
+                });
+            };
+
+            let bits = self.bits_after(prev_block);
+            let median_time_past = self.median_time_past_after(prev_block);
+            if prev_block.header.index + 1 != block.header.index {
+                return Err(BlockValidationErr::MismatchedIndex {
+                    expected: prev_block.header.index + 1,
+                    actual: block.header.index,
+                });
+            } else if block.header.bits != bits {
+                return Err(BlockValidationErr::InvalidDifficulty {
+                    expected: bits,
+                    actual: block.header.bits,
+                });
+            } else if block.header.timestamp <= median_time_past {
+                return Err(BlockValidationErr::TimestampTooEarly {
+                    median_time_past,
+                    actual: block.header.timestamp,
+                });
             }
         }
 
-        if let Some((coinbase, transactions)) = block.transactions.split_first() {
-            if !coinbase.is_coinbase() {
-                return Err(BlockValidationErr::InvalidCoinbaseTransaction);
+        if self.extends_tip(block) {
+            self.check_transactions(&self.unspent_outputs, block)
+        } else {
+            let prev_block = self.block(&block.header.prev_block_hash).unwrap();
+            self.check_transactions(&self.unspent_outputs_after(prev_block), block)
+        }
+    }
+
+    pub fn update_with_block(
+        &mut self,
+        block: Block,
+    ) -> Result<Vec<ChainEvent>, BlockValidationErr> {
+        self.validate_block(&block)?;
+
+        if let Some(store) = &mut self.store {
+            store
+                .append(&block)
+                .map_err(|err| BlockValidationErr::StorageFailure(err.kind()))?;
+        }
+
+        self.connect_block(block)
+    }
+
+    fn connect_block(&mut self, block: Block) -> Result<Vec<ChainEvent>, BlockValidationErr> {
+        let hash = block.hash;
+        let parent_work = self
+            .chain_work
+            .get(&block.header.prev_block_hash)
+            .copied()
+            .unwrap_or(U256::ZERO);
+        let work = parent_work.saturating_add(target::block_work(block.header.bits));
+
+        self.chain_work.insert(hash, work);
+
+        if self.extends_tip(&block) {
+            let undo = apply_transactions(&mut self.unspent_outputs, &block);
+            self.blocks.push(block);
+            self.undo.push(undo);
+
+            return Ok(vec![ChainEvent::BlockConnected(hash)]);
+        }
+
+        self.side_blocks.insert(hash, block);
+
+        if work <= self.chain_work() {
+            return Ok(vec![]);
+        }
+
+        self.reorganize(hash)
+    }
+
+    fn extends_tip(&self, block: &Block) -> bool {
+        self.blocks
+            .last()
+            .is_none_or(|tip| tip.hash == block.header.prev_block_hash)
+    }
+
+    pub fn disconnect_tip(&mut self) -> io::Result<Option<Block>> {
+        let Some(hash) = self.blocks.last().map(|tip| tip.hash) else {
+            return Ok(None);
+        };
+
+        self.discard(&hash)?;
+        let block = self.rewind_tip();
+
+        if let Some(store) = &mut self.store {
+            store.set_tip(&block.header.prev_block_hash)?;
+        }
+
+        Ok(Some(block))
+    }
+
+    fn reorganize(&mut self, hash: Hash256) -> Result<Vec<ChainEvent>, BlockValidationErr> {
+        let mut branch = vec![];
+        let mut current = &self.side_blocks[&hash];
+        loop {
+            branch.push(current.hash);
+            match self.side_blocks.get(&current.header.prev_block_hash) {
+                Some(prev_block) => current = prev_block,
+                None => break,
             }
+        }
+        branch.reverse();
 
-            let mut block_spent: HashSet<Hash> = HashSet::new();
-            let mut block_created: HashSet<Hash> = HashSet::new();
-            let mut total_fee = 0;
+        let fork_height = current.header.index as usize;
+        let mut events = vec![];
+        let mut disconnected = vec![];
 
-            for transaction in transactions {
-                let input_hashes = transaction.input_hashes();
+        while fork_height < self.blocks.len() {
+            let block = self.rewind_tip();
+            events.push(ChainEvent::BlockDisconnected(block.hash));
+            disconnected.push(block.hash);
+            self.side_blocks.insert(block.hash, block);
+        }
 
-                if !(&input_hashes - &self.unspent_outputs).is_empty()
-                    || !(&input_hashes & &block_spent).is_empty()
-                {
-                    return Err(BlockValidationErr::InvalidInput);
+        for (i, hash) in branch.iter().enumerate() {
+            match self.check_transactions(&self.unspent_outputs, &self.side_blocks[hash]) {
+                Ok(()) => {
+                    let block = self.side_blocks.remove(hash).unwrap();
+                    let undo = apply_transactions(&mut self.unspent_outputs, &block);
+                    events.push(ChainEvent::BlockConnected(*hash));
+                    self.blocks.push(block);
+                    self.undo.push(undo);
                 }
+                Err(err) => {
+                    while fork_height < self.blocks.len() {
+                        let block = self.rewind_tip();
+                        self.side_blocks.insert(block.hash, block);
+                    }
+
+                    for hash in disconnected.iter().rev() {
+                        let block = self.side_blocks.remove(hash).unwrap();
+                        let undo = apply_transactions(&mut self.unspent_outputs, &block);
+                        self.blocks.push(block);
+                        self.undo.push(undo);
+                    }
 
-                let input_value = transaction.input_value();
-                let output_value = transaction.output_value();
+                    self.discard(&branch[i])
+                        .map_err(|err| BlockValidationErr::StorageFailure(err.kind()))?;
 
-                if input_value < output_value {
-                    return Err(BlockValidationErr::InsufficientInputValue);
+                    return Err(err);
                 }
+            }
+        }
 
-                let fee = input_value - output_value;
+        Ok(events)
+    }
 
-                total_fee += fee;
+    fn check_transactions(
+        &self,
+        unspent_outputs: &HashMap<OutPoint, UnspentOutput>,
+        block: &Block,
+    ) -> Result<(), BlockValidationErr> {
+        let Some((coinbase, transactions)) = block.transactions.split_first() else {
+            return Ok(());
+        };
 
-                block_spent.extend(input_hashes);
-                block_created.extend(transaction.output_hashes());
-            }
+        let height = block.header.index;
+        let block_created = block
+            .transactions
+            .iter()
+            .enumerate()
+            .flat_map(|(tx, transaction)| {
+                transaction
+                    .outpoints()
+                    .into_iter()
+                    .map(move |(outpoint, output)| {
+                        let unspent = UnspentOutput {
+                            output,
+                            height,
+                            coinbase: 0 == tx,
+                        };
+
+                        (outpoint, (tx, unspent))
+                    })
+            })
+            .collect::<HashMap<OutPoint, (usize, UnspentOutput)>>();
+        let coinbase_maturity = self.params.coinbase_maturity;
+        let threads = self.validation_threads().min(transactions.len()).max(1);
+        let chunk_size = transactions.len().div_ceil(threads).max(1);
+        let check_chunk = |(chunk, transactions): (usize, &[Transaction])| {
+            transactions
+                .iter()
+                .enumerate()
+                .map(|(i, transaction)| {
+                    let tx = chunk * chunk_size + i + 1;
+                    check_transaction(tx, transaction, height, coinbase_maturity, |outpoint| {
+                        unspent_outputs.get(outpoint).or_else(|| {
+                            block_created
+                                .get(outpoint)
+                                .filter(|(created_tx, _)| *created_tx < tx)
+                                .map(|(_, unspent)| unspent)
+                        })
+                    })
+                })
+                .collect::<Vec<Result<u64, BlockValidationErr>>>()
+        };
 
-            if coinbase.output_value() < total_fee {
-                return Err(BlockValidationErr::InvalidCoinbaseTransactionFee);
-            } else {
-                block_created.extend(coinbase.output_hashes());
+        let fees = if 1 < threads {
+            thread::scope(|scope| {
+                transactions
+                    .chunks(chunk_size)
+                    .enumerate()
+                    .map(|chunk| scope.spawn(move || check_chunk(chunk)))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap())
+                    .collect::<Vec<Result<u64, BlockValidationErr>>>()
+            })
+        } else {
+            check_chunk((0, transactions))
+        };
+
+        let mut block_spent: HashSet<&OutPoint> = HashSet::new();
+        let mut total_fee: u64 = 0;
+
+        for ((tx, transaction), fee) in transactions.iter().enumerate().zip(fees) {
+            let fee = fee?;
+
+            for (index, input) in transaction.inputs.iter().enumerate() {
+                if !block_spent.insert(&input.outpoint) {
+                    return Err(BlockValidationErr::InvalidInput {
+                        tx: tx + 1,
+                        input: index,
+                        outpoint: input.outpoint.clone(),
+                    });
+                }
             }
 
-            self.unspent_outputs
-                .retain(|output| !block_spent.contains(output));
-            self.unspent_outputs.extend(block_created);
+            total_fee = total_fee
+                .checked_add(fee)
+                .filter(|&total_fee| money_range(total_fee))
+                .ok_or(BlockValidationErr::ValueOutOfRange { tx: tx + 1 })?;
+        }
+
+        let coinbase_value = coinbase
+            .output_value()
+            .ok_or(BlockValidationErr::ValueOutOfRange { tx: 0 })?;
+        let maximum = self.params.block_subsidy(height).saturating_add(total_fee);
+
+        if coinbase_value < total_fee {
+            Err(BlockValidationErr::InvalidCoinbaseTransactionFee {
+                minimum: total_fee,
+                actual: coinbase_value,
+            })
+        } else if coinbase_value > maximum {
+            Err(BlockValidationErr::ExcessiveCoinbaseValue {
+                maximum,
+                actual: coinbase_value,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn unspent_outputs_after<'a>(
+        &'a self,
+        mut last: &'a Block,
+    ) -> HashMap<OutPoint, UnspentOutput> {
+        let mut branch = vec![];
+        while !self.is_active(last) {
+            branch.push(last);
+            last = self.block(&last.header.prev_block_hash).unwrap();
         }
 
-        self.blocks.push(block);
+        let mut unspent_outputs = self.unspent_outputs.clone();
+        for undo in self.undo[last.header.index as usize + 1..].iter().rev() {
+            undo.revert(&mut unspent_outputs);
+        }
+        for block in branch.into_iter().rev() {
+            apply_transactions(&mut unspent_outputs, block);
+        }
+
+        unspent_outputs
+    }
+
+    fn rewind_tip(&mut self) -> Block {
+        let block = self.blocks.pop().unwrap();
+        self.undo.pop().unwrap().revert(&mut self.unspent_outputs);
+
+        block
+    }
+
+    fn discard(&mut self, hash: &Hash256) -> io::Result<()> {
+        let mut discarded = vec![*hash];
+        let mut i = 0;
+        while let Some(parent) = discarded.get(i).copied() {
+            discarded.extend(
+                self.side_blocks
+                    .values()
+                    .filter(|block| block.header.prev_block_hash == parent)
+                    .map(|block| block.hash),
+            );
+            i += 1;
+        }
+
+        if let Some(store) = &mut self.store {
+            for hash in discarded.iter().rev() {
+                store.remove(hash)?;
+            }
+        }
+
+        for hash in &discarded {
+            self.side_blocks.remove(hash);
+            self.chain_work.remove(hash);
+        }
 
         Ok(())
     }
+
+    fn is_active(&self, block: &Block) -> bool {
+        self.blocks
+            .get(block.header.index as usize)
+            .is_some_and(|active| active.hash == block.hash)
+    }
+
+    fn ancestor<'a>(&'a self, mut block: &'a Block, height: u32) -> &'a Block {
+        while height < block.header.index {
+            if self.is_active(block) {
+                return &self.blocks[height as usize];
+            }
+            block = self.block(&block.header.prev_block_hash).unwrap();
+        }
+
+        block
+    }
+
+    fn median_time_past_after<'a>(&'a self, mut last: &'a Block) -> u128 {
+        let mut timestamps = vec![last.header.timestamp];
+
+        while timestamps.len() < self.params.median_time_span.max(1) && 0 < last.header.index {
+            last = self.block(&last.header.prev_block_hash).unwrap();
+            timestamps.push(last.header.timestamp);
+        }
+        timestamps.sort_unstable();
+
+        timestamps[timestamps.len() / 2]
+    }
+
+    fn bits_after(&self, last: &Block) -> u32 {
+        let height = last.header.index as usize + 1;
+        let window = self.params.difficulty_window.max(2) as usize;
+
+        if !height.is_multiple_of(window) {
+            return last.header.bits;
+        }
+
+        let first = self.ancestor(last, (height - window) as u32);
+        let expected = (self.params.target_block_interval * (window as u128 - 1)).max(1);
+        let actual = last
+            .header
+            .timestamp
+            .saturating_sub(first.header.timestamp)
+            .clamp(expected / 4, expected * 4);
+
+        let pow_limit = target::target(self.params.initial_bits).unwrap_or(U256::MAX);
+        let target = target::target(last.header.bits).unwrap_or(pow_limit);
+        let target = match target.checked_mul(U256::from(actual)) {
+            Some(scaled) => scaled / U256::from(expected),
+            None => pow_limit,
+        };
+
+        target.clamp(U256::ONE, pow_limit).to_compact()
+    }
+}
+
+pub fn check_transaction<'a, F>(
+    tx: usize,
+    transaction: &Transaction,
+    height: u32,
+    coinbase_maturity: u32,
+    unspent_output: F,
+) -> Result<u64, BlockValidationErr>
+where
+    F: Fn(&OutPoint) -> Option<&'a UnspentOutput>,
+{
+    if 0 != transaction.height {
+        return Err(BlockValidationErr::InvalidTransactionHeight {
+            tx,
+            actual: transaction.height,
+        });
+    }
+
+    let sighash = transaction.sighash();
+    let mut spent = HashSet::new();
+    let mut input_value: u64 = 0;
+
+    for (index, input) in transaction.inputs.iter().enumerate() {
+        let unspent = match unspent_output(&input.outpoint) {
+            Some(unspent) if spent.insert(&input.outpoint) => unspent,
+            _ => {
+                return Err(BlockValidationErr::InvalidInput {
+                    tx,
+                    input: index,
+                    outpoint: input.outpoint.clone(),
+                })
+            }
+        };
+        let output = &unspent.output;
+
+        let mature_height = unspent.mature_height(coinbase_maturity);
+        if height < mature_height {
+            return Err(BlockValidationErr::PrematureCoinbaseSpend {
+                tx,
+                input: index,
+                mature_height,
+                height,
+            });
+        }
+
+        if address(&input.public_key) != output.to_addr
+            || !verify_signature(&input.public_key, &sighash, &input.signature)
+        {
+            return Err(BlockValidationErr::InvalidSignature { tx, input: index });
+        }
+
+        input_value = input_value
+            .checked_add(output.value)
+            .filter(|&input_value| money_range(input_value))
+            .ok_or(BlockValidationErr::ValueOutOfRange { tx })?;
+    }
+
+    let output_value = transaction
+        .output_value()
+        .ok_or(BlockValidationErr::ValueOutOfRange { tx })?;
+
+    if input_value < output_value {
+        return Err(BlockValidationErr::InsufficientInputValue {
+            tx,
+            input_value,
+            output_value,
+        });
+    }
+
+    Ok(input_value - output_value)
+}
+
+fn apply_transactions(
+    unspent_outputs: &mut HashMap<OutPoint, UnspentOutput>,
+    block: &Block,
+) -> BlockUndo {
+    let height = block.header.index;
+    let mut spent = vec![];
+    let mut created = HashSet::new();
+
+    for (tx, transaction) in block.transactions.iter().enumerate() {
+        for input in &transaction.inputs {
+            let unspent = unspent_outputs.remove(&input.outpoint).unwrap();
+            if !created.remove(&input.outpoint) {
+                spent.push((input.outpoint.clone(), unspent));
+            }
+        }
+
+        for (outpoint, output) in transaction.outpoints() {
+            let unspent = UnspentOutput {
+                output,
+                height,
+                coinbase: 0 == tx,
+            };
+            created.insert(outpoint.clone());
+            unspent_outputs.insert(outpoint, unspent);
+        }
+    }
+
+    BlockUndo {
+        spent,
+        created: created.into_iter().collect(),
+    }
 }
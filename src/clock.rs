@@ -0,0 +1,44 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> u128;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock(Arc<Mutex<u128>>);
+
+impl ManualClock {
+    pub fn new(now: u128) -> Self {
+        ManualClock(Arc::new(Mutex::new(now)))
+    }
+
+    pub fn set(&self, now: u128) {
+        *self.0.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, millis: u128) {
+        let mut now = self.0.lock().unwrap();
+        *now = now.saturating_add(millis);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u128 {
+        *self.0.lock().unwrap()
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::{self, Display, Formatter};
+
+pub const ENCODING_VERSION: u8 = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeErr {
+    InvalidUtf8,
+    NonCanonicalVarint,
+    TrailingBytes,
+    UnexpectedEnd,
+    UnsupportedVersion(u8),
+    VarintOverflow,
+}
+
+impl Display for DecodeErr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DecodeErr::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            DecodeErr::NonCanonicalVarint => write!(f, "varint is not minimally encoded"),
+            DecodeErr::TrailingBytes => write!(f, "trailing bytes after encoded value"),
+            DecodeErr::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeErr::UnsupportedVersion(version) => {
+                write!(f, "unsupported encoding version {}", version)
+            }
+            DecodeErr::VarintOverflow => write!(f, "varint does not fit in 64 bits"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeErr {}
+
+pub trait Encodable {
+    fn encode(&self, bytes: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.encode(&mut bytes);
+
+        bytes
+    }
+}
+
+pub trait Decodable: Sized {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr>;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeErr> {
+        let mut decoder = Decoder::new(bytes);
+        let value = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+
+        Ok(value)
+    }
+}
+
+pub fn encode_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while 0x80 <= value {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+pub fn encode_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    encode_varint(bytes, value.len() as u64);
+    bytes.extend(value);
+}
+
+pub fn encode_list<T: Encodable>(bytes: &mut Vec<u8>, items: &[T]) {
+    encode_varint(bytes, items.len() as u64);
+    for item in items {
+        item.encode(bytes);
+    }
+}
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Decoder { bytes }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn finish(&self) -> Result<(), DecodeErr> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeErr::TrailingBytes)
+        }
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeErr> {
+        if self.bytes.len() < len {
+            return Err(DecodeErr::UnexpectedEnd);
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeErr> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeErr> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeErr> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeErr> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    pub fn u128(&mut self) -> Result<u128, DecodeErr> {
+        self.array().map(u128::from_le_bytes)
+    }
+
+    pub fn version(&mut self) -> Result<u8, DecodeErr> {
+        match self.u8()? {
+            ENCODING_VERSION => Ok(ENCODING_VERSION),
+            version => Err(DecodeErr::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn varint(&mut self) -> Result<u64, DecodeErr> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            let bits = (byte & 0x7f) as u64;
+            if 0 < shift && 0 == byte {
+                return Err(DecodeErr::NonCanonicalVarint);
+            }
+            if bits << shift >> shift != bits {
+                return Err(DecodeErr::VarintOverflow);
+            }
+
+            value |= bits << shift;
+            if 0 == byte & 0x80 {
+                return Ok(value);
+            }
+        }
+
+        Err(DecodeErr::VarintOverflow)
+    }
+
+    pub fn count(&mut self) -> Result<usize, DecodeErr> {
+        let len = self.varint()?;
+
+        if (self.bytes.len() as u64) < len {
+            return Err(DecodeErr::UnexpectedEnd);
+        }
+
+        Ok(len as usize)
+    }
+
+    pub fn bytes(&mut self) -> Result<Vec<u8>, DecodeErr> {
+        let len = self.count()?;
+
+        self.take(len).map(|bytes| bytes.to_vec())
+    }
+
+    pub fn string(&mut self) -> Result<String, DecodeErr> {
+        String::from_utf8(self.bytes()?).map_err(|_| DecodeErr::InvalidUtf8)
+    }
+
+    pub fn list<T: Decodable>(&mut self) -> Result<Vec<T>, DecodeErr> {
+        let count = self.count()?;
+        let mut items = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            items.push(T::decode(self)?);
+        }
+
+        Ok(items)
+    }
+}
@@ -1,9 +1,83 @@
-use super::types::Hash;
+use {
+    super::types::Hash256,
+    blake2::{digest::consts::U32, Blake2b, Digest},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    DoubleSha256,
+    Blake2b,
+    Blake3,
+    Scrypt(ScryptParams),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+impl ScryptParams {
+    pub fn new(log_n: u8, r: u32, p: u32) -> Option<Self> {
+        scrypt::Params::new(log_n, r, p, Hash256::LEN).ok()?;
+
+        Some(ScryptParams { log_n, r, p })
+    }
+
+    pub fn log_n(&self) -> u8 {
+        self.log_n
+    }
+
+    pub fn r(&self) -> u32 {
+        self.r
+    }
+
+    pub fn p(&self) -> u32 {
+        self.p
+    }
+}
+
+impl HashAlgorithm {
+    pub const LITECOIN_SCRYPT: HashAlgorithm = HashAlgorithm::Scrypt(ScryptParams {
+        log_n: 10,
+        r: 1,
+        p: 1,
+    });
+
+    pub fn scrypt(log_n: u8, r: u32, p: u32) -> Option<Self> {
+        ScryptParams::new(log_n, r, p).map(HashAlgorithm::Scrypt)
+    }
+
+    pub fn digest(&self, bytes: &[u8]) -> Hash256 {
+        match *self {
+            HashAlgorithm::Sha256 => Hash256::sha256(bytes),
+            HashAlgorithm::DoubleSha256 => Hash256::sha256(Hash256::sha256(bytes).as_bytes()),
+            HashAlgorithm::Blake2b => Hash256(Blake2b::<U32>::digest(bytes).into()),
+            HashAlgorithm::Blake3 => Hash256(blake3::hash(bytes).into()),
+            HashAlgorithm::Scrypt(ScryptParams { log_n, r, p }) => {
+                let params = scrypt::Params::new(log_n, r, p, Hash256::LEN)
+                    .expect("scrypt parameters are checked by ScryptParams::new");
+                let mut hash = Hash256::ZERO;
+                scrypt::scrypt(bytes, bytes, &params, &mut hash.0)
+                    .expect("scrypt output is 32 bytes");
+
+                hash
+            }
+        }
+    }
+}
 
 pub trait Hashable {
     fn bytes(&self) -> Vec<u8>;
 
-    fn hash(&self) -> Hash {
-        crypto_hash::digest(crypto_hash::Algorithm::SHA256, &self.bytes())
+    fn hash(&self) -> Hash256 {
+        self.hash_with(HashAlgorithm::default())
+    }
+
+    fn hash_with(&self, algorithm: HashAlgorithm) -> Hash256 {
+        algorithm.digest(&self.bytes())
     }
 }
@@ -1,25 +1,44 @@
 pub mod block;
 pub mod blockchain;
+pub mod clock;
+pub mod encoding;
 pub mod hashable;
+pub mod mempool;
+pub mod merkle;
+pub mod miner;
+pub mod params;
+pub mod store;
+pub mod target;
+pub mod template;
 pub mod transaction;
 pub mod types;
 pub mod utility;
+pub mod wallet;
 
-use {block::Block, blockchain::Blockchain, transaction::Transaction, utility::now};
+use {
+    block::Block,
+    blockchain::Blockchain,
+    hashable::Hashable,
+    mempool::Mempool,
+    miner::Miner,
+    template::BlockTemplate,
+    transaction::{Input, OutPoint, Transaction},
+    types::Hash256,
+    wallet::Wallet,
+};
 
 #[no_mangle]
 pub extern "C" fn run() {
     let max_block = 10;
     let index = 0;
-    let mut timestamp = now();
-    let mut prev_block_hash = vec![0; 32];
-    let user_a = "Alice".to_owned();
+    let prev_block_hash = Hash256::ZERO;
+    let user_a = Wallet::from_seed(b"Alice");
     let mut user_a_coins = 50;
-    let user_b = "Bob".to_owned();
+    let user_b = Wallet::from_seed(b"Bob");
     let user_b_coins = 12;
-    let user_c = "Chris".to_owned();
-    let user_c_coins = 536;
-    let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    let user_c = Wallet::from_seed(b"Chris");
+    let mut blockchain = Blockchain::new();
+    let timestamp = blockchain.now();
 
     let mut genesis_block = Block::new(
         index,
@@ -29,95 +48,331 @@ pub extern "C" fn run() {
             inputs: vec![],
             outputs: vec![
                 transaction::Output {
-                    to_addr: user_a.clone(),
+                    to_addr: user_a.address(),
                     value: user_a_coins,
                 },
                 transaction::Output {
-                    to_addr: user_b.clone(),
+                    to_addr: user_b.address(),
                     value: user_b_coins,
                 },
             ],
+            height: index,
+            extra_nonce: 0,
         }],
-        difficulty,
+        blockchain.next_bits(),
     );
-    genesis_block.mine();
+    Miner::for_params(blockchain.params())
+        .mine(&mut genesis_block)
+        .expect("Failed to mine block");
     println!("Mined Genesis Block: {genesis_block:?}");
 
-    let mut blockchain = Blockchain::new();
     blockchain
         .update_with_block(genesis_block)
         .expect("Failed to add genesis block");
 
-    for i in 1..=max_block {
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
-        let fee = 2;
-        if user_b_coins < user_a_coins {
-            user_a_coins -= user_b_coins;
-        } else {
-            // overspending
-            user_a_coins = 2 * fee;
+    let mut mempool = Mempool::new(100_000);
+
+    let maturity = blockchain.params().coinbase_maturity as usize;
+
+    for i in 1..=max_block + maturity {
+        if maturity < i {
+            let source = i - maturity - 1;
+            let fee = 2;
+            if user_b_coins < user_a_coins {
+                user_a_coins -= user_b_coins;
+            } else {
+                // overspending
+                user_a_coins = 2 * fee;
+            }
+            user_a_coins -= fee;
+            let spender = if 0 == source { &user_a } else { &user_c };
+            mempool
+                .add(
+                    &blockchain,
+                    spender.sign(Transaction {
+                        inputs: vec![Input::new(OutPoint {
+                            txid: blockchain.blocks[source].transactions[0].hash(),
+                            index: 0,
+                        })],
+                        outputs: vec![
+                            transaction::Output {
+                                to_addr: user_a.address(),
+                                value: user_a_coins,
+                            },
+                            transaction::Output {
+                                to_addr: user_b.address(),
+                                value: user_b_coins,
+                            },
+                        ],
+                        height: 0,
+                        extra_nonce: 0,
+                    }),
+                )
+                .unwrap_or_else(|_| panic!("Failed to add transaction {i}"));
         }
-        user_a_coins -= fee;
-        let mut block = block::Block::new(
-            i,
-            timestamp,
-            prev_block_hash,
-            vec![
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![transaction::Output {
-                        to_addr: user_c.clone(),
-                        value: user_c_coins,
-                    }],
-                },
-                Transaction {
-                    inputs: vec![
-                        blockchain.blocks[i as usize - 1].transactions[0].outputs[0].clone()
-                    ],
-                    outputs: vec![
-                        transaction::Output {
-                            to_addr: user_a.clone(),
-                            value: user_a_coins,
-                        },
-                        transaction::Output {
-                            to_addr: user_b.clone(),
-                            value: user_b_coins,
-                        },
-                    ],
-                },
-            ],
-            difficulty,
-        );
 
-        block.mine();
+        let block = BlockTemplate::new(user_c.address())
+            .mine(&blockchain, &mempool)
+            .expect("Failed to mine block");
         println!("Mined Block {i}: {block:?}");
-        blockchain
+        let events = blockchain
             .update_with_block(block)
-            .expect(&format!("Failed to add block {i}"));
+            .unwrap_or_else(|err| panic!("Failed to add block {i}: {err}"));
+        mempool.update(&blockchain, &events);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::blockchain::BlockValidationErr::*;
+    use crate::{
+        blockchain::{BlockValidationErr::*, ChainEvent::*, UnspentOutput},
+        clock::Clock,
+        encoding::Encodable,
+    };
+    use std::{fs, io::Write, path::PathBuf, sync::Arc};
+
+    const TIMESTAMP: u128 = 1_000_000;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("sediment-{}-{}.blocks", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+
+        path
+    }
 
     #[test]
     fn test_good_run() {
         run();
     }
 
+    #[test]
+    fn test_good_block_store_recovery() {
+        let path = temp_path("recovery");
+        let timestamp = TIMESTAMP;
+        let user_a = Wallet::from_seed(b"Alice");
+        let bits = 0x2000_ffff;
+        let mine = |index, prev_block_hash| {
+            let mut block = Block::new(
+                index,
+                timestamp + index as u128,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 1,
+                    }],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let genesis_block = mine(0, Hash256::ZERO);
+        let genesis_hash = genesis_block.hash;
+        let mut blockchain = Blockchain::open(&path).expect("Failed to open blockchain");
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+        let genesis_len = fs::metadata(&path).unwrap().len();
+        blockchain
+            .update_with_block(mine(1, genesis_hash))
+            .expect("Failed to add block");
+        drop(blockchain);
+
+        let file_len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(file_len - 3)
+            .unwrap();
+
+        let store = store::BlockStore::open(&path).expect("Failed to open block store");
+        assert_eq!(store.len(), genesis_len);
+        assert_eq!(fs::metadata(&path).unwrap().len(), genesis_len);
+        assert_eq!(store.offsets_by_height(1), &[] as &[u64]);
+        drop(store);
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.len(), 1);
+        blockchain
+            .update_with_block(mine(1, genesis_hash))
+            .expect("Failed to add block");
+        drop(blockchain);
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"SDMT garbage")
+            .unwrap();
+
+        let blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.len(), 2);
+        assert_eq!(fs::metadata(&path).unwrap().len(), file_len);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_good_block_subsidy() {
+        let params = params::Params {
+            initial_reward: 100,
+            halving_interval: 2,
+            tail_emission: 10,
+            ..params::Params::default()
+        };
+
+        assert_eq!(
+            (0..10)
+                .map(|height| params.block_subsidy(height))
+                .collect::<Vec<u64>>(),
+            vec![100, 100, 50, 50, 25, 25, 12, 12, 10, 10]
+        );
+        assert_eq!(params.block_subsidy(u32::MAX), 10);
+    }
+
+    #[test]
+    fn test_good_block_template() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+
+        let mut empty_blockchain = Blockchain::new();
+        empty_blockchain.set_clock(clock::ManualClock::new(TIMESTAMP));
+        let mut block = BlockTemplate::new(user_a.address())
+            .build(&empty_blockchain, &Vec::<Transaction>::new());
+        assert_eq!(block.header.index, 0);
+        assert_eq!(block.header.timestamp, TIMESTAMP);
+        assert_eq!(block.header.prev_block_hash, Hash256::ZERO);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(
+            block.transactions[0].output_value(),
+            Some(empty_blockchain.params().block_subsidy(0))
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        assert!(empty_blockchain.update_with_block(block).is_ok());
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: (0..3)
+                    .map(|_| transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 30,
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
+            }],
+            0x2000_ffff,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let genesis_hash = genesis_block.hash;
+        let coinbase_txid = genesis_block.transactions[0].hash();
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain.set_clock(clock::ManualClock::new(TIMESTAMP + 60));
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = |signer: &Wallet, index, outputs: u32, fee: u64| {
+            signer.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: coinbase_txid,
+                    index,
+                })],
+                outputs: (0..outputs)
+                    .map(|output| transaction::Output {
+                        to_addr: user_b.address(),
+                        value: if 0 == output { 30 - fee } else { 0 },
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+        let cheap = spend(&user_a, 0, 1, 1);
+        let expensive = spend(&user_a, 0, 1, 20);
+        let bulky = spend(&user_a, 1, 20, 25);
+        let forged = spend(&user_b, 2, 1, 29);
+        let source = vec![cheap.clone(), forged, bulky.clone(), expensive.clone()];
+
+        let coinbase_size = BlockTemplate::new(user_b.address())
+            .build(&blockchain, &Vec::<Transaction>::new())
+            .transactions[0]
+            .to_bytes()
+            .len();
+        let template = |max_size| BlockTemplate {
+            max_size,
+            ..BlockTemplate::new(user_b.address())
+        };
+
+        let block = template(1_000_000).build(&blockchain, &source);
+        assert_eq!(block.header.index, 1);
+        assert_eq!(block.header.prev_block_hash, genesis_hash);
+        assert_eq!(block.header.timestamp, TIMESTAMP + 60);
+        assert_eq!(block.header.bits, blockchain.next_bits());
+        assert_eq!(block.header.merkle_root, block.merkle_root());
+        assert_eq!(block.transactions[1..], [expensive.clone(), bulky.clone()]);
+        assert_eq!(
+            block.transactions[0].output_value(),
+            Some(blockchain.params().block_subsidy(1) + 45)
+        );
+
+        let max_size = coinbase_size + expensive.to_bytes().len() + bulky.to_bytes().len();
+        assert_eq!(
+            template(max_size)
+                .build(&blockchain, &source)
+                .transactions
+                .len(),
+            3
+        );
+
+        let mut block = template(max_size - 1).build(&blockchain, &source);
+        assert_eq!(block.transactions[1..], [expensive]);
+        assert_eq!(
+            block.transactions[0].output_value(),
+            Some(blockchain.params().block_subsidy(1) + 20)
+        );
+
+        let max_size = coinbase_size + cheap.to_bytes().len() - 1;
+        assert_eq!(
+            template(max_size)
+                .build(&blockchain, &source)
+                .transactions
+                .len(),
+            1
+        );
+
+        block.mine_sha256().expect("Failed to mine block");
+        assert!(blockchain.update_with_block(block).is_ok());
+        assert_eq!(
+            template(1_000_000).build(&blockchain, &source).transactions[1..],
+            [bulky]
+        );
+    }
+
     #[test]
     fn test_good_blockchain() {
         let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
         let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
+        let user_b = Wallet::from_seed(b"Bob");
         let user_b_coins = 7;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+        let bits = 0x2000_ffff;
 
         let mut genesis_block = Block::new(
             index,
@@ -127,27 +382,47 @@ mod tests {
                 inputs: vec![],
                 outputs: vec![
                     transaction::Output {
-                        to_addr: user_a.clone(),
+                        to_addr: user_a.address(),
                         value: user_a_coins,
                     },
                     transaction::Output {
-                        to_addr: user_b.clone(),
+                        to_addr: user_b.address(),
                         value: user_b_coins,
                     },
                 ],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
 
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
 
         index += 1;
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut transaction = Transaction {
+            inputs: (0..2)
+                .map(|index| {
+                    Input::new(OutPoint {
+                        txid: blockchain.blocks[0].transactions[0].hash(),
+                        index,
+                    })
+                })
+                .collect(),
+            outputs: blockchain.blocks[0].transactions[0].outputs.clone(),
+            height: 0,
+            extra_nonce: 0,
+        };
+        user_a.sign_input(&mut transaction, 0);
+        user_b.sign_input(&mut transaction, 1);
         let mut block = Block::new(
             index,
             timestamp,
@@ -156,153 +431,418 @@ mod tests {
                 Transaction {
                     inputs: vec![],
                     outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
                 },
-                Transaction {
-                    inputs: blockchain.blocks[0].transactions[0].outputs.clone(),
-                    outputs: blockchain.blocks[0].transactions[0].outputs.clone(),
-                },
+                transaction,
             ],
-            difficulty,
+            bits,
         );
-        block.mine();
+        block.mine_sha256().expect("Failed to mine block");
 
-        assert!(!blockchain.update_with_block(block).is_err());
+        assert!(blockchain.update_with_block(block).is_ok());
     }
 
     #[test]
-    fn test_error_achronological_timestamp() {
-        let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    fn test_good_blockchain_open() {
+        let path = temp_path("open");
+        let timestamp = TIMESTAMP;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+        let mine = |index, prev_block_hash, to_addr| {
+            let mut block = Block::new(
+                index,
+                timestamp + index as u128,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output { to_addr, value: 1 }],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
 
-        let mut genesis_block = Block::new(
-            index,
-            timestamp,
-            prev_block_hash,
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![],
-            }],
-            difficulty,
+        let genesis_block = mine(0, Hash256::ZERO, user_a.address());
+        let block_a1 = mine(1, genesis_block.hash, user_a.address());
+        let block_b1 = mine(1, genesis_block.hash, user_b.address());
+        let (genesis_hash, hash_a1, hash_b1) = (genesis_block.hash, block_a1.hash, block_b1.hash);
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to open blockchain");
+        assert!(blockchain.blocks.is_empty());
+        for block in [genesis_block, block_a1, block_b1] {
+            blockchain
+                .update_with_block(block)
+                .expect("Failed to add block");
+        }
+        let unspent_outputs = blockchain.unspent_outputs().clone();
+        drop(blockchain);
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.len(), 2);
+        assert_eq!(blockchain.blocks[1].hash, hash_a1);
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
+        assert!(blockchain.block(&hash_b1).is_some());
+
+        let block_a2 = mine(2, hash_a1, user_a.address());
+        let hash_a2 = block_a2.hash;
+        blockchain
+            .update_with_block(block_a2)
+            .expect("Failed to add block");
+        drop(blockchain);
+
+        let mut store = store::BlockStore::open(&path).expect("Failed to open block store");
+        assert_eq!(store.offset_by_hash(&genesis_hash), Some(0));
+        assert_eq!(store.offsets_by_height(1).len(), 2);
+        assert_eq!(store.offsets_by_height(2).len(), 1);
+        assert_eq!(
+            store.block_by_hash(&hash_b1).unwrap().unwrap().hash,
+            hash_b1
         );
-        genesis_block.mine();
+        assert_eq!(store.blocks().unwrap().len(), 4);
+
+        drop(store);
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.len(), 3);
+        let block_a2 = blockchain
+            .disconnect_tip()
+            .expect("Failed to disconnect block")
+            .unwrap();
+        assert_eq!(block_a2.hash, hash_a2);
+        drop(blockchain);
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.len(), 2);
+        assert!(blockchain.block(&hash_a2).is_none());
+        assert!(blockchain.block(&hash_b1).is_some());
+        blockchain
+            .update_with_block(block_a2)
+            .expect("Failed to add block");
+        drop(blockchain);
+
+        assert_eq!(Blockchain::open(&path).unwrap().blocks.len(), 3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_good_blockchain_reopen() {
+        let path = temp_path("reopen");
+        let timestamp = TIMESTAMP;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+        let mine = |index, timestamp, prev_block_hash, to_addr| {
+            let mut block = Block::new(
+                index,
+                timestamp,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output { to_addr, value: 1 }],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let genesis_block = mine(0, timestamp, Hash256::ZERO, user_a.address());
+        let mut prev_block_hash = genesis_block.hash;
+        let mut blocks = vec![genesis_block];
+        for index in 1..=2 {
+            blocks.push(mine(
+                index,
+                timestamp + index as u128,
+                prev_block_hash,
+                user_a.address(),
+            ));
+            prev_block_hash = blocks.last().unwrap().hash;
+        }
+        prev_block_hash = blocks[0].hash;
+        for index in 1..=4 {
+            blocks.push(mine(
+                index,
+                timestamp + index as u128,
+                prev_block_hash,
+                user_b.address(),
+            ));
+            prev_block_hash = blocks.last().unwrap().hash;
+        }
+        let hash_b2 = blocks[4].hash;
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to open blockchain");
+        for block in blocks {
+            blockchain
+                .update_with_block(block)
+                .expect("Failed to add block");
+        }
+        assert_eq!(blockchain.blocks.last().unwrap().hash, prev_block_hash);
+        for _ in 0..2 {
+            blockchain
+                .disconnect_tip()
+                .expect("Failed to disconnect block")
+                .unwrap();
+        }
+        assert_eq!(blockchain.blocks.last().unwrap().hash, hash_b2);
+        let unspent_outputs = blockchain.unspent_outputs().clone();
+        drop(blockchain);
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.last().unwrap().hash, hash_b2);
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
+
+        let drift = blockchain.params().max_future_drift;
+        blockchain.set_clock(clock::ManualClock::new(timestamp + 3_600_000));
+        let block_b3 = mine(3, timestamp + 3_600_000 + drift, hash_b2, user_b.address());
+        let hash_b3 = block_b3.hash;
+        blockchain
+            .update_with_block(block_b3)
+            .expect("Failed to add block");
+        drop(blockchain);
+
+        let blockchain = Blockchain::open(&path).expect("Failed to reopen blockchain");
+        assert_eq!(blockchain.blocks.last().unwrap().hash, hash_b3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_good_chain_reorganization() {
+        let timestamp = TIMESTAMP;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+        let mine = |index, prev_block_hash, to_addr| {
+            let mut block = Block::new(
+                index,
+                timestamp + index as u128,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output { to_addr, value: 1 }],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let genesis_block = mine(0, Hash256::ZERO, user_a.address());
+        let block_a1 = mine(1, genesis_block.hash, user_a.address());
+        let block_b1 = mine(1, genesis_block.hash, user_b.address());
+        let block_b2 = mine(2, block_b1.hash, user_b.address());
+        let (hash_a1, hash_b1, hash_b2) = (block_a1.hash, block_b1.hash, block_b2.hash);
+        let coinbase_a1 = OutPoint {
+            txid: block_a1.transactions[0].hash(),
+            index: 0,
+        };
+        let coinbase_b1 = OutPoint {
+            txid: block_b1.transactions[0].hash(),
+            index: 0,
+        };
 
         let mut blockchain = Blockchain::new();
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
+        assert_eq!(
+            blockchain.update_with_block(block_a1),
+            Ok(vec![BlockConnected(hash_a1)])
+        );
+        let work = blockchain.chain_work();
+
+        assert_eq!(blockchain.update_with_block(block_b1), Ok(vec![]));
+        assert_eq!(blockchain.blocks.last().unwrap().hash, hash_a1);
+        assert_eq!(blockchain.chain_work(), work);
+        assert!(blockchain.block(&hash_b1).is_some());
 
-        index += 1;
-        timestamp -= 1;
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
-        let mut block = Block::new(index, timestamp, prev_block_hash, vec![], difficulty);
-        block.mine();
         assert_eq!(
-            blockchain.update_with_block(block),
-            Err(AchronologicalTimestamp)
+            blockchain.update_with_block(block_b2),
+            Ok(vec![
+                BlockDisconnected(hash_a1),
+                BlockConnected(hash_b1),
+                BlockConnected(hash_b2),
+            ])
         );
+        assert_eq!(blockchain.blocks.len(), 3);
+        assert_eq!(blockchain.blocks[1].hash, hash_b1);
+        assert_eq!(blockchain.blocks[2].hash, hash_b2);
+        assert!(blockchain.chain_work() > work);
+        assert!(blockchain.block(&hash_a1).is_some());
+        assert_eq!(blockchain.unspent_output(&coinbase_a1), None);
+        assert!(blockchain.unspent_output(&coinbase_b1).is_some());
     }
 
     #[test]
-    fn test_error_insufficient_input_value() {
-        let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
-        let user_a_coins = 50;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    fn test_good_chained_transactions() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let user_c = Wallet::from_seed(b"Chris");
+        let bits = 0x2000_ffff;
 
         let mut genesis_block = Block::new(
-            index,
-            timestamp,
-            prev_block_hash,
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
             vec![Transaction {
                 inputs: vec![],
                 outputs: vec![transaction::Output {
-                    to_addr: user_a.clone(),
-                    value: user_a_coins,
+                    to_addr: user_a.address(),
+                    value: 50,
                 }],
+                height: 0,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
 
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
+        let unspent_outputs = blockchain.unspent_outputs().clone();
 
-        index += 1;
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
+        let a_to_b = user_a.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: blockchain.blocks[0].transactions[0].hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_b.address(),
+                value: 48,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let b_to_c = user_b.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: a_to_b.hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_c.address(),
+                value: 45,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![transaction::Output {
+                to_addr: user_c.address(),
+                value: blockchain.params().block_subsidy(1) + 5,
+            }],
+            height: 1,
+            extra_nonce: 0,
+        };
         let mut block = Block::new(
-            index,
-            timestamp,
-            prev_block_hash,
-            vec![
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![blockchain.blocks[0].transactions[0].outputs[0].clone()],
-                    outputs: vec![transaction::Output {
-                        to_addr: user_a.clone(),
-                        value: user_a_coins * 2,
-                    }],
-                },
-            ],
-            difficulty,
+            1,
+            blockchain.blocks[0].header.timestamp + 1,
+            blockchain.blocks[0].hash,
+            vec![coinbase, a_to_b.clone(), b_to_c.clone()],
+            bits,
         );
-        block.mine();
+        block.mine_sha256().expect("Failed to mine block");
+
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+        assert_eq!(blockchain.unspent_outputs().len(), 2);
+        assert!(blockchain
+            .unspent_output(&a_to_b.outpoints()[0].0)
+            .is_none());
         assert_eq!(
-            blockchain.update_with_block(block),
-            Err(InsufficientInputValue)
+            blockchain
+                .unspent_output(&b_to_c.outpoints()[0].0)
+                .map(|unspent| &unspent.output),
+            Some(&b_to_c.outputs[0])
         );
+
+        blockchain
+            .disconnect_tip()
+            .expect("Failed to disconnect block")
+            .unwrap();
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
     }
 
     #[test]
-    fn test_error_invalid_coinbase_transaction() {
-        let index = 0;
-        let timestamp = now();
-        let prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
-        let user_a_coins = 50;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    fn test_good_difficulty_retarget() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let params = params::Params {
+            difficulty_window: 2,
+            target_block_interval: 2,
+            ..params::Params::default()
+        };
+        let bits = params.initial_bits;
+        let mut blockchain = Blockchain::with_params(params);
+
+        for _ in 0..2 {
+            let mut block = Block::new(
+                index,
+                timestamp,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                blockchain.next_bits(),
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            blockchain
+                .update_with_block(block)
+                .expect("Failed to add block");
+
+            index += 1;
+            timestamp += 1;
+            prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        }
+        assert_eq!(
+            blockchain.next_bits(),
+            (target::target(bits).unwrap() >> 1).to_compact()
+        );
 
         let mut block = Block::new(
             index,
             timestamp,
             prev_block_hash,
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_addr: user_a,
-                    value: user_a_coins,
-                }],
+                inputs: vec![],
                 outputs: vec![],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            blockchain.next_bits(),
         );
-        block.mine();
+        block.mine_sha256().expect("Failed to mine block");
 
-        let mut blockchain = Blockchain::new();
-        assert_eq!(
-            blockchain.update_with_block(block),
-            Err(InvalidCoinbaseTransaction)
-        );
+        assert!(blockchain.update_with_block(block).is_ok());
     }
 
     #[test]
-    fn test_error_invalid_coinbase_transaction_fee() {
+    fn test_good_disconnect_tip() {
         let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
         let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
-        let user_b_coins = 7;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
 
         let mut genesis_block = Block::new(
             index,
@@ -310,35 +850,41 @@ mod tests {
             prev_block_hash,
             vec![Transaction {
                 inputs: vec![],
-                outputs: vec![
-                    transaction::Output {
-                        to_addr: user_a.clone(),
-                        value: user_a_coins,
-                    },
-                    transaction::Output {
-                        to_addr: user_b.clone(),
-                        value: user_b_coins,
-                    },
-                ],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
 
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        assert_eq!(
+            blockchain
+                .disconnect_tip()
+                .expect("Failed to disconnect block")
+                .map(|block| block.hash),
+            None
+        );
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
+        let unspent_outputs = blockchain.unspent_outputs().clone();
+        let total_supply = blockchain.total_supply();
 
         index += 1;
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
-        let zero_value_transaction_vector = vec![transaction::Output {
-            to_addr: user_a.clone(),
-            value: 0,
-        }];
-        let positive_value_transaction_vector =
-            vec![blockchain.blocks[0].transactions[0].outputs[0].clone()];
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let spent = OutPoint {
+            txid: blockchain.blocks[0].transactions[0].hash(),
+            index: 0,
+        };
         let mut block = Block::new(
             index,
             timestamp,
@@ -346,73 +892,2216 @@ mod tests {
             vec![
                 Transaction {
                     inputs: vec![],
-                    outputs: zero_value_transaction_vector.clone(),
-                },
-                Transaction {
-                    inputs: positive_value_transaction_vector.clone(),
-                    outputs: zero_value_transaction_vector.clone(),
+                    outputs: vec![transaction::Output {
+                        to_addr: user_b.address(),
+                        value: blockchain.params().block_subsidy(index),
+                    }],
+                    height: index,
+                    extra_nonce: 0,
                 },
+                user_a.sign(Transaction {
+                    inputs: vec![Input::new(spent.clone())],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_b.address(),
+                        value: user_a_coins,
+                    }],
+                    height: 0,
+                    extra_nonce: 0,
+                }),
             ],
-            difficulty,
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        let hash = block.hash;
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+
+        let undo = blockchain.block_undo(index).unwrap();
+        assert_eq!(
+            undo.spent,
+            vec![(
+                spent.clone(),
+                UnspentOutput {
+                    output: blockchain.blocks[0].transactions[0].outputs[0].clone(),
+                    height: 0,
+                    coinbase: true,
+                }
+            )]
         );
-        block.mine();
+        assert_eq!(undo.created.len(), 2);
+        assert_eq!(blockchain.unspent_output(&spent), None);
+
+        let block = blockchain
+            .disconnect_tip()
+            .expect("Failed to disconnect block")
+            .unwrap();
+        assert_eq!(block.hash, hash);
+        assert_eq!(blockchain.blocks.len(), 1);
+        assert_eq!(blockchain.block_undo(index), None);
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
+        assert_eq!(blockchain.total_supply(), total_supply);
 
         assert_eq!(
             blockchain.update_with_block(block),
-            Err(InvalidCoinbaseTransactionFee)
+            Ok(vec![BlockConnected(hash)])
         );
     }
 
     #[test]
-    fn test_error_invalid_genesis_block_format() {
-        let index = 0;
-        let timestamp = now();
-        let prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
-        let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
-        let user_b_coins = 7;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    fn test_good_encoding_round_trip() {
+        use encoding::{encode_varint, Decodable, Decoder};
 
-        let mut genesis_block = Block::new(
-            index,
-            timestamp,
-            prev_block_hash,
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![
-                    transaction::Output {
-                        to_addr: user_a.clone(),
-                        value: user_a_coins,
-                    },
-                    transaction::Output {
-                        to_addr: user_b.clone(),
-                        value: user_b_coins,
-                    },
-                ],
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![transaction::Output {
+                to_addr: user_a.address(),
+                value: 50,
             }],
-            difficulty,
+            height: 1,
+            extra_nonce: 0,
+        };
+        let transaction = user_a.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: coinbase.hash(),
+                index: 0,
+            })],
+            outputs: vec![
+                transaction::Output {
+                    to_addr: user_b.address(),
+                    value: 20,
+                },
+                transaction::Output {
+                    to_addr: String::new(),
+                    value: u64::MAX,
+                },
+            ],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let mut block = Block::new(
+            1,
+            TIMESTAMP,
+            Hash256([7; 32]),
+            vec![coinbase, transaction],
+            bits,
         );
-        genesis_block.mine();
-        genesis_block.prev_block_hash = genesis_block.hash.clone();
+        block.mine_sha256().expect("Failed to mine block");
 
-        let mut blockchain = Blockchain::new();
-        assert_eq!(
-            blockchain.update_with_block(genesis_block),
-            Err(InvalidGenesisBlockFormat)
+        let bytes = block.to_bytes();
+        let decoded = Block::from_bytes(&bytes).expect("Failed to decode block");
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.hash, block.hash);
+        assert_eq!(decoded.header.hash(), block.header.hash());
+        assert_eq!(decoded.txids(), block.txids());
+        assert_eq!(decoded.transactions, block.transactions);
+
+        for transaction in &block.transactions {
+            let bytes = transaction.to_bytes();
+            assert_eq!(Transaction::from_bytes(&bytes).as_ref(), Ok(transaction));
+            for output in &transaction.outputs {
+                assert_eq!(
+                    transaction::Output::from_bytes(&output.to_bytes()).as_ref(),
+                    Ok(output)
+                );
+            }
+        }
+
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX] {
+            let mut bytes = vec![];
+            encode_varint(&mut bytes, value);
+            let mut decoder = Decoder::new(&bytes);
+            assert_eq!(decoder.varint(), Ok(value));
+            assert_eq!(decoder.finish(), Ok(()));
+        }
+
+        let split = |addrs: &[&str]| Transaction {
+            inputs: vec![],
+            outputs: addrs
+                .iter()
+                .map(|to_addr| transaction::Output {
+                    to_addr: to_addr.to_string(),
+                    value: 0,
+                })
+                .collect(),
+            height: 0,
+            extra_nonce: 0,
+        };
+        assert_ne!(
+            split(&["ab\0\0\0\0\0\0\0\0c"]).hash(),
+            split(&["ab", "c"]).hash()
+        );
+    }
+
+    #[test]
+    fn test_good_hash256() {
+        use encoding::{Decodable, DecodeErr};
+
+        let hash = Hash256::sha256(b"sediment");
+        let hex = hash.to_string();
+
+        assert_eq!(hex.len(), 2 * Hash256::LEN);
+        assert_eq!(hex.parse::<Hash256>(), Ok(hash));
+        assert_eq!(format!("{hash:?}"), format!("Hash256({hex})"));
+        assert_eq!(Hash256::from_slice(hash.as_bytes()), Some(hash));
+        assert_eq!(Hash256::try_from(&hash.to_vec()[..]).ok(), Some(hash));
+        assert_eq!(<[u8; 32]>::from(hash), hash.0);
+        assert_eq!(Hash256::from([0; 32]), Hash256::ZERO);
+        assert_eq!(Hash256::default(), Hash256::ZERO);
+        assert!(Hash256::ZERO.is_zero());
+        assert!(!hash.is_zero());
+        assert!(Hash256::ZERO < Hash256([0xff; 32]));
+        assert!(Hash256([1; 32]) < Hash256([2; 32]));
+
+        assert_eq!(Hash256::from_slice(&[0; 31]), None);
+        assert!(Hash256::try_from(&[0; 33][..]).is_err());
+        assert_eq!(
+            hex[2..].parse::<Hash256>(),
+            Err(hex::FromHexError::InvalidStringLength)
+        );
+        assert!("zz".repeat(Hash256::LEN).parse::<Hash256>().is_err());
+
+        let mut bytes = vec![];
+        hash.encode(&mut bytes);
+        assert_eq!(bytes, hash.to_vec());
+        assert_eq!(Hash256::from_bytes(&bytes), Ok(hash));
+        assert_eq!(
+            Hash256::from_bytes(&bytes[1..]),
+            Err(DecodeErr::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn test_good_hash_algorithms() {
+        use hashable::HashAlgorithm::*;
+
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let bits = 0x2000_ffff;
+        let scrypt = hashable::HashAlgorithm::scrypt(4, 1, 1).unwrap();
+
+        for (algorithm, hex) in [
+            (
+                Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                DoubleSha256,
+                "4f8b42c22dd3729b519ba6f68d2da7cc5b2d606d05daed5ad5128cc03e6c6358",
+            ),
+            (
+                Blake2b,
+                "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+            ),
+            (
+                Blake3,
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+            (
+                scrypt,
+                "d99463424ef013bd4824f1ff5a4038e64205cf09336244b748d7986f20043107",
+            ),
+        ] {
+            assert_eq!(algorithm.digest(b"abc"), hex.parse().unwrap());
+        }
+
+        let params = params::Params {
+            block_hash: Blake3,
+            pow_hash: scrypt,
+            ..params::Params::default()
+        };
+        let miner = miner::Miner {
+            threads: 1,
+            ..miner::Miner::for_params(&params)
+        };
+        let genesis_block = || {
+            Block::new(
+                index,
+                timestamp,
+                Hash256::ZERO,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            )
+        };
+
+        let mut block = genesis_block();
+        miner.mine(&mut block).expect("Failed to mine block");
+        assert_eq!(block.hash, block.header.hash_with(Blake3));
+        assert!(block::check_difficulty(
+            &block.header.hash_with(scrypt),
+            bits
+        ));
+        assert_eq!(block.check_with(Blake3, scrypt), Ok(()));
+
+        let mut default_block = genesis_block();
+        default_block.mine_sha256().expect("Failed to mine block");
+        let (expected, actual) = (default_block.header.hash_with(Blake3), default_block.hash);
+
+        let mut blockchain = Blockchain::with_params(params);
+        assert_eq!(
+            blockchain.update_with_block(default_block),
+            Err(InvalidHash { expected, actual })
+        );
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add genesis block");
+
+        blockchain.set_clock(clock::ManualClock::new(timestamp + 60));
+        let template = BlockTemplate::new(Wallet::from_seed(b"Alice").address());
+        let block = template
+            .mine(&blockchain, &Vec::<Transaction>::new())
+            .expect("Failed to mine block");
+        assert_eq!(block.hash, block.header.hash_with(Blake3));
+        assert_eq!(block.check_with(Blake3, scrypt), Ok(()));
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+
+        let mut block = template.build(&blockchain, &Vec::<Transaction>::new());
+        block.mine_sha256().expect("Failed to mine block");
+        assert!(matches!(
+            blockchain.update_with_block(block),
+            Err(InvalidHash { .. })
+        ));
+    }
+
+    #[test]
+    fn test_good_identical_outputs() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                ],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let txid = blockchain.blocks[0].transactions[0].hash();
+        for output_index in 0..2 {
+            index += 1;
+            timestamp += 1;
+            prev_block_hash = blockchain.blocks.last().unwrap().hash;
+            let mut block = Block::new(
+                index,
+                timestamp,
+                prev_block_hash,
+                vec![
+                    Transaction {
+                        inputs: vec![],
+                        outputs: vec![],
+                        height: index,
+                        extra_nonce: 0,
+                    },
+                    user_a.sign(Transaction {
+                        inputs: vec![Input::new(OutPoint {
+                            txid,
+                            index: output_index,
+                        })],
+                        outputs: vec![transaction::Output {
+                            to_addr: user_a.address(),
+                            value: user_a_coins,
+                        }],
+                        height: 0,
+                        extra_nonce: 0,
+                    }),
+                ],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+
+            assert!(blockchain.update_with_block(block).is_ok());
+        }
+        assert_eq!(
+            blockchain.unspent_output(&OutPoint { txid, index: 1 }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_good_mempool() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: (0..5)
+                    .map(|_| transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 10,
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let coinbase_txid = genesis_block.transactions[0].hash();
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = |index, fee: u64| {
+            user_a.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: coinbase_txid,
+                    index,
+                })],
+                outputs: vec![transaction::Output {
+                    to_addr: user_b.address(),
+                    value: 10 - fee,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+        let size = spend(0, 0).to_bytes().len();
+
+        let mut mempool = mempool::Mempool::new(3 * size);
+        let txid_0 = mempool
+            .add(&blockchain, spend(0, 2))
+            .expect("Failed to add transaction");
+        let txid_1 = mempool
+            .add(&blockchain, spend(1, 4))
+            .expect("Failed to add transaction");
+        let txid_2 = mempool
+            .add(&blockchain, spend(2, 1))
+            .expect("Failed to add transaction");
+        assert_eq!(mempool.len(), 3);
+        assert_eq!(mempool.size(), 3 * size);
+        assert_eq!(
+            mempool
+                .entries()
+                .iter()
+                .map(|entry| entry.txid)
+                .collect::<Vec<_>>(),
+            vec![txid_1, txid_0, txid_2]
+        );
+        assert_eq!(mempool.get(&txid_1).unwrap().fee, 4);
+        assert_eq!(mempool.get(&txid_1).unwrap().fee_rate(), 4.0 / size as f64);
+
+        let txid_3 = mempool
+            .add(&blockchain, spend(3, 3))
+            .expect("Failed to add transaction");
+        assert_eq!(mempool.len(), 3);
+        assert!(!mempool.contains(&txid_2));
+        assert!(mempool
+            .spender(&OutPoint {
+                txid: coinbase_txid,
+                index: 2,
+            })
+            .is_none());
+        assert_eq!(
+            mempool.spender(&mempool.get(&txid_3).unwrap().transaction.inputs[0].outpoint),
+            Some(&txid_3)
+        );
+
+        let conflict = spend(0, 0);
+        let mut block = Block::new(
+            1,
+            blockchain.blocks[0].header.timestamp + 1,
+            blockchain.blocks[0].hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_b.address(),
+                        value: 4,
+                    }],
+                    height: 1,
+                    extra_nonce: 0,
+                },
+                spend(1, 4),
+                conflict,
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let events = blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+        mempool.update(&blockchain, &events);
+        assert_eq!(mempool.len(), 1);
+        assert!(mempool.contains(&txid_3));
+        assert_eq!(mempool.size(), size);
+
+        mempool
+            .add(&blockchain, spend(2, 1))
+            .expect("Failed to add transaction");
+        assert_eq!(mempool.len(), 2);
+
+        let block = blockchain
+            .disconnect_tip()
+            .expect("Failed to disconnect block")
+            .unwrap();
+        let events = vec![BlockDisconnected(block.hash)];
+        mempool.update(&blockchain, &events);
+        assert_eq!(mempool.len(), 2);
+        mempool
+            .add(&blockchain, spend(1, 4))
+            .expect("Failed to add transaction");
+        assert_eq!(mempool.entries()[0].txid, txid_1);
+    }
+
+    #[test]
+    fn test_good_mempool_expiry() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let clock = clock::ManualClock::new(1_000_000);
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain.set_clock(clock.clone());
+
+        let mut genesis_block = BlockTemplate::new(user_a.address()).build(&blockchain, &[][..]);
+        assert_eq!(genesis_block.header.timestamp, clock.now());
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let coinbase_txid = genesis_block.transactions[0].hash();
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = |value| {
+            user_a.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: coinbase_txid,
+                    index: 0,
+                })],
+                outputs: vec![transaction::Output {
+                    to_addr: user_b.address(),
+                    value,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+
+        let mut mempool = Mempool::new(100_000);
+        mempool.set_expiry(1_000);
+        let txid_0 = mempool
+            .add(&blockchain, spend(90))
+            .expect("Failed to add transaction");
+        assert_eq!(mempool.get(&txid_0).unwrap().time, 1_000_000);
+
+        clock.advance(999);
+        mempool.update(&blockchain, &[]);
+        assert!(mempool.contains(&txid_0));
+
+        clock.advance(1);
+        mempool.update(&blockchain, &[]);
+        assert!(mempool.is_empty());
+        assert_eq!(mempool.size(), 0);
+
+        let txid_1 = mempool
+            .add(&blockchain, spend(80))
+            .expect("Failed to add transaction");
+        clock.advance(500);
+        assert_eq!(mempool.expire(clock.now()), 0);
+        assert_eq!(mempool.expire(clock.now() + 500), 1);
+        assert!(!mempool.contains(&txid_1));
+    }
+
+    #[test]
+    fn test_good_merkle_proof() {
+        use encoding::{Decodable, DecodeErr};
+
+        for leaf_count in 1..=9u8 {
+            let txids = (0..leaf_count)
+                .map(|i| Hash256([i; 32]))
+                .collect::<Vec<Hash256>>();
+            let root = merkle::merkle_root(&txids);
+
+            for (index, txid) in txids.iter().enumerate() {
+                let proof = merkle::MerkleProof::new(&txids, index).unwrap();
+                let decoded = merkle::MerkleProof::from_bytes(&proof.to_bytes()).unwrap();
+
+                assert_eq!(decoded, proof);
+                assert!(proof.verify(txid, &root));
+                assert!(!proof.verify(&Hash256([0xff; 32]), &root));
+            }
+            assert_eq!(merkle::MerkleProof::new(&txids, txids.len()), None);
+        }
+
+        let bytes = merkle::MerkleProof::new(&[Hash256::ZERO; 3], 0)
+            .unwrap()
+            .to_bytes();
+        assert_eq!(bytes[0], encoding::ENCODING_VERSION);
+        assert_eq!(
+            merkle::MerkleProof::from_bytes(&[0; 7]),
+            Err(DecodeErr::UnsupportedVersion(0))
+        );
+        assert_eq!(
+            merkle::MerkleProof::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeErr::UnexpectedEnd)
+        );
+        assert_eq!(
+            merkle::MerkleProof::from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(DecodeErr::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn test_good_merkle_proof_in_blockchain() {
+        use encoding::Decodable;
+
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                ],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut transactions = vec![Transaction {
+            inputs: vec![],
+            outputs: vec![],
+            height: index,
+            extra_nonce: 0,
+        }];
+        for output_index in 0..2 {
+            transactions.push(user_a.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: blockchain.blocks[0].transactions[0].hash(),
+                    index: output_index,
+                })],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            }));
+        }
+        let mut block = Block::new(index, timestamp, prev_block_hash, transactions, bits);
+        block.mine_sha256().expect("Failed to mine block");
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+
+        let txid = blockchain.blocks[1].transactions[2].hash();
+        let (height, proof) = blockchain.merkle_proof(&txid).unwrap();
+        let header = &blockchain.blocks[height as usize].header;
+
+        assert_eq!(height, index);
+        assert_eq!(proof.index, 2);
+        assert!(merkle::MerkleProof::from_bytes(&proof.to_bytes())
+            .unwrap()
+            .verify(&txid, &header.merkle_root));
+        assert!(!proof.verify(&txid, &blockchain.blocks[0].header.merkle_root));
+        assert_eq!(blockchain.merkle_proof(&Hash256::ZERO), None);
+    }
+
+    #[test]
+    fn test_good_merkle_root() {
+        let txids = (0..3u8).map(|i| Hash256([i; 32])).collect::<Vec<Hash256>>();
+
+        assert_eq!(merkle::merkle_root(&[]), Hash256::ZERO);
+        assert_eq!(merkle::merkle_root(&txids[..1]), txids[0]);
+        assert_eq!(
+            merkle::merkle_root(&txids[..2]),
+            merkle::merkle_parent(&txids[0], &txids[1])
+        );
+        assert_eq!(
+            merkle::merkle_root(&txids),
+            merkle::merkle_parent(&merkle::merkle_parent(&txids[0], &txids[1]), &txids[2])
+        );
+        assert_ne!(
+            merkle::merkle_root(&[txids[1], txids[0]]),
+            merkle::merkle_root(&txids[..2])
+        );
+    }
+
+    #[test]
+    fn test_good_mining() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let block = || {
+            Block::new(
+                0,
+                TIMESTAMP,
+                Hash256::ZERO,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 50,
+                    }],
+                    height: 0,
+                    extra_nonce: 0,
+                }],
+                0x1f00_ffff,
+            )
+        };
+
+        let mut single = block();
+        let stats = miner::Miner::new(1)
+            .mine(&mut single)
+            .expect("Failed to mine block");
+        assert!(block::check_difficulty(&single.hash, single.header.bits));
+        assert_eq!(single.hash, single.header.hash());
+        assert_eq!(stats.hashes, single.header.nonce + 1);
+        assert_eq!(stats.threads, 1);
+        assert!(0.0 <= stats.hash_rate());
+
+        for threads in [2, 3, 8] {
+            let mut parallel = block();
+            parallel.header.timestamp = single.header.timestamp;
+            let stats = miner::Miner::new(threads)
+                .mine(&mut parallel)
+                .expect("Failed to mine block");
+            assert_eq!(parallel.header.nonce, single.header.nonce);
+            assert_eq!(parallel.hash, single.hash);
+            assert!(single.header.nonce < stats.hashes);
+        }
+
+        let mut offset = block();
+        offset.header.timestamp = single.header.timestamp;
+        let miner = miner::Miner {
+            nonces: single.header.nonce + 1..u64::MAX,
+            ..miner::Miner::new(4)
+        };
+        miner.mine(&mut offset).expect("Failed to mine block");
+        assert!(single.header.nonce < offset.header.nonce);
+        assert!(block::check_difficulty(&offset.hash, offset.header.bits));
+    }
+
+    #[test]
+    fn test_good_mining_rolls() {
+        use miner::MiningEvent::*;
+
+        let timestamp = 1_000;
+        let user_a = Wallet::from_seed(b"Alice");
+        let mut block = Block::new(
+            0,
+            timestamp,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: 50,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            0x2000_ffff,
+        );
+        let merkle_root = block.header.merkle_root;
+        let miner = miner::Miner {
+            nonces: 0..2,
+            max_timestamp: Some(timestamp + 2),
+            clock: Arc::new(clock::ManualClock::new(timestamp)),
+            ..miner::Miner::new(1)
+        };
+        let mut events = vec![];
+
+        let stats = miner
+            .mine_with(&mut block, |event| match event {
+                NoncesExhausted(stats) => assert_eq!(stats.hashes % 2, 0),
+                TimestampRolled(_) | ExtraNonceRolled(_) => events.push(format!("{event:?}")),
+            })
+            .expect("Failed to mine block");
+
+        let extra_nonce = block.transactions[0].extra_nonce;
+        assert!(0 < extra_nonce);
+        assert_eq!(
+            stats.hashes,
+            2 * events.len() as u64 + block.header.nonce + 1
+        );
+        assert_eq!(events[0], format!("{:?}", TimestampRolled(timestamp + 1)));
+        assert_eq!(events[1], format!("{:?}", TimestampRolled(timestamp + 2)));
+        assert_eq!(events[2], format!("{:?}", ExtraNonceRolled(1)));
+        assert_eq!(
+            events.last(),
+            Some(&format!("{:?}", ExtraNonceRolled(extra_nonce)))
+        );
+        assert_eq!(block.header.timestamp, timestamp + 2);
+        assert_ne!(block.header.merkle_root, merkle_root);
+        assert_eq!(block.header.merkle_root, block.merkle_root());
+
+        let mut blockchain = Blockchain::new();
+        assert!(blockchain.update_with_block(block).is_ok());
+
+        blockchain.set_clock(clock::ManualClock::new(timestamp + 10));
+        let miner = miner::Miner::for_blockchain(&blockchain);
+        assert_eq!(
+            miner.max_timestamp,
+            Some(timestamp + 10 + blockchain.params().max_future_drift)
+        );
+        assert_eq!(miner.clock.now(), timestamp + 10);
+
+        let miner = miner::Miner {
+            threads: 1,
+            nonces: 0..1,
+            ..miner
+        };
+        let mut block =
+            BlockTemplate::new(user_a.address()).build(&blockchain, &Vec::<Transaction>::new());
+        let mut rolled = 0;
+        miner
+            .mine_with(&mut block, |event| {
+                if let TimestampRolled(_) = event {
+                    rolled += 1;
+                }
+            })
+            .expect("Failed to mine block");
+        assert_eq!(block.header.timestamp, timestamp + 10 + rolled);
+        assert!(blockchain.update_with_block(block).is_ok());
+    }
+
+    #[test]
+    fn test_good_parallel_validation() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: (0..16)
+                    .map(|_| transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 1,
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let coinbase_txid = genesis_block.transactions[0].hash();
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = |signer: &Wallet, index| {
+            signer.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: coinbase_txid,
+                    index,
+                })],
+                outputs: vec![transaction::Output {
+                    to_addr: user_b.address(),
+                    value: 1,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+        let block = |transactions: Vec<Transaction>| {
+            let mut block = Block::new(
+                1,
+                blockchain.blocks[0].header.timestamp + 1,
+                blockchain.blocks[0].hash,
+                [Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: 1,
+                    extra_nonce: 0,
+                }]
+                .into_iter()
+                .chain(transactions)
+                .collect(),
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let valid_block = block((0..16).map(|index| spend(&user_a, index)).collect());
+
+        let mut transactions = (0..16)
+            .map(|index| spend(&user_a, index))
+            .collect::<Vec<Transaction>>();
+        transactions[12] = spend(&user_b, 12);
+        transactions[5] = spend(&user_a, 2);
+        let double_spend_block = block(transactions);
+
+        let mut transactions = (0..16)
+            .map(|index| spend(&user_a, index))
+            .collect::<Vec<Transaction>>();
+        transactions[3] = spend(&user_b, 3);
+        transactions[11] = spend(&user_a, 2);
+        let invalid_signature_block = block(transactions);
+
+        for threads in [1, 3, 16] {
+            blockchain.set_validation_threads(threads);
+            assert_eq!(blockchain.validation_threads(), threads);
+
+            assert_eq!(blockchain.validate_block(&valid_block), Ok(()));
+            assert_eq!(
+                blockchain.validate_block(&double_spend_block),
+                Err(InvalidInput {
+                    tx: 6,
+                    input: 0,
+                    outpoint: OutPoint {
+                        txid: coinbase_txid,
+                        index: 2,
+                    },
+                })
+            );
+            assert_eq!(
+                blockchain.validate_block(&invalid_signature_block),
+                Err(InvalidSignature { tx: 4, input: 0 })
+            );
+        }
+
+        blockchain.set_validation_threads(0);
+        assert!(0 < blockchain.validation_threads());
+        assert!(blockchain.update_with_block(valid_block).is_ok());
+        assert_eq!(blockchain.total_supply(), 16);
+    }
+
+    #[test]
+    fn test_good_target() {
+        use target::U256;
+
+        let pow_limit = 0x2000_ffff;
+        let target = U256::from_compact(0x1d00_ffff).unwrap();
+        assert_eq!(target, U256::from(0xffffu64) << 208);
+        assert_eq!(target.to_compact(), 0x1d00_ffff);
+        assert_eq!(U256::from(0x80u64).to_compact(), 0x0200_8000);
+        assert_eq!(U256::from_compact(0x0200_8000), Some(U256::from(0x80u64)));
+        assert_eq!(U256::from_compact(0x0480_0001), None);
+        assert_eq!(U256::from_compact(0xff00_ffff), None);
+        assert_eq!(target::target(0), None);
+        assert_eq!(format!("{}", U256::from(0xabcu64)), "0xabc");
+
+        assert_eq!(U256::MAX.checked_mul(U256::from(2u64)), None);
+        assert_eq!(
+            (U256::ONE << 200).checked_mul(U256::ONE << 55),
+            Some(U256::ONE << 255)
+        );
+        assert_eq!(U256::MAX / (U256::ONE << 255), U256::ONE);
+        assert_eq!(
+            U256::from(u128::MAX) / U256::from(0xffffu64),
+            U256::from(u128::MAX / 0xffff)
+        );
+
+        assert_eq!(target::difficulty(pow_limit, pow_limit), 1.0);
+        assert_eq!(target::difficulty(0x1f00_ffff, pow_limit), 256.0);
+        assert_eq!(target::bits_for_difficulty(256.0, pow_limit), 0x1f00_ffff);
+        assert_eq!(target::bits_for_difficulty(0.5, pow_limit), pow_limit);
+
+        assert_eq!(target::block_work(pow_limit), U256::from(256u64));
+        assert_eq!(target::block_work(0), U256::ZERO);
+        assert!(target::block_work(pow_limit) < target::block_work(0x1f00_ffff));
+
+        assert!(block::check_difficulty(&Hash256::ZERO, pow_limit));
+        assert!(!block::check_difficulty(&Hash256([0xff; 32]), pow_limit));
+        assert!(!block::check_difficulty(&Hash256::ZERO, 0));
+    }
+
+    #[test]
+    fn test_good_total_supply() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+        assert_eq!(blockchain.total_supply(), user_a_coins);
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let fee = 5;
+        let subsidy = blockchain.params().block_subsidy(index);
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_b.address(),
+                        value: subsidy + fee,
+                    }],
+                    height: index,
+                    extra_nonce: 0,
+                },
+                user_a.sign(Transaction {
+                    inputs: vec![Input::new(OutPoint {
+                        txid: blockchain.blocks[0].transactions[0].hash(),
+                        index: 0,
+                    })],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_b.address(),
+                        value: user_a_coins - fee,
+                    }],
+                    height: 0,
+                    extra_nonce: 0,
+                }),
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+
+        assert_eq!(blockchain.total_supply(), user_a_coins + subsidy);
+    }
+
+    #[test]
+    fn test_good_validate_block() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: 50,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        assert_eq!(genesis_block.check_sha256(), Ok(()));
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        assert_eq!(blockchain.validate_block(&genesis_block), Ok(()));
+        assert!(blockchain.blocks.is_empty());
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = |value| {
+            user_a.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: blockchain.blocks[0].transactions[0].hash(),
+                    index: 0,
+                })],
+                outputs: vec![transaction::Output {
+                    to_addr: user_b.address(),
+                    value,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+        let block = |transaction| {
+            let mut block = Block::new(
+                1,
+                blockchain.blocks[0].header.timestamp + 1,
+                blockchain.blocks[0].hash,
+                vec![
+                    Transaction {
+                        inputs: vec![],
+                        outputs: vec![],
+                        height: 1,
+                        extra_nonce: 0,
+                    },
+                    transaction,
+                ],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let invalid_block = block(spend(51));
+        assert_eq!(invalid_block.check_sha256(), Ok(()));
+        assert_eq!(
+            blockchain.validate_block(&invalid_block),
+            Err(InsufficientInputValue {
+                tx: 1,
+                input_value: 50,
+                output_value: 51,
+            })
+        );
+
+        let valid_block = block(spend(50));
+        let unspent_outputs = blockchain.unspent_outputs().clone();
+        assert_eq!(blockchain.validate_block(&valid_block), Ok(()));
+        assert_eq!(blockchain.validate_block(&valid_block), Ok(()));
+        assert_eq!(blockchain.blocks.len(), 1);
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
+
+        let hash = valid_block.hash;
+        blockchain
+            .update_with_block(valid_block)
+            .expect("Failed to add block");
+        assert_eq!(blockchain.blocks.len(), 2);
+        assert_eq!(
+            blockchain.validate_block(&blockchain.blocks[1]),
+            Err(DuplicateBlock { hash })
+        );
+    }
+
+    #[test]
+    fn test_error_blockchain_open() {
+        let path = temp_path("replay");
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            0x2000_ffff,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let genesis_hash = genesis_block.hash;
+
+        let mut blockchain = Blockchain::open(&path).expect("Failed to open blockchain");
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+        drop(blockchain);
+
+        let params = params::Params {
+            initial_bits: 0x1f00_ffff,
+            ..params::Params::default()
+        };
+        let err = Blockchain::open_with_params(&path, params).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "stored block {} is invalid: {}",
+                genesis_hash,
+                InvalidDifficulty {
+                    expected: 0x1f00_ffff,
+                    actual: 0x2000_ffff,
+                }
+            )
+        );
+
+        assert_eq!(Blockchain::open(&path).unwrap().blocks.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_error_decoding() {
+        use encoding::{Decodable, DecodeErr, Decoder, ENCODING_VERSION};
+
+        let user_a = Wallet::from_seed(b"Alice");
+        let mut block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: 50,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            0x2000_ffff,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        let bytes = block.to_bytes();
+
+        for len in 0..bytes.len() {
+            assert!(Block::from_bytes(&bytes[..len]).is_err());
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Block::from_bytes(&trailing).err(),
+            Some(DecodeErr::TrailingBytes)
+        );
+
+        let mut versioned = bytes.clone();
+        versioned[0] = ENCODING_VERSION + 1;
+        assert_eq!(
+            Block::from_bytes(&versioned).err(),
+            Some(DecodeErr::UnsupportedVersion(ENCODING_VERSION + 1))
+        );
+
+        assert_eq!(
+            Decoder::new(&[0x80, 0x00]).varint(),
+            Err(DecodeErr::NonCanonicalVarint)
+        );
+        assert_eq!(
+            Decoder::new(&[0xff; 10]).varint(),
+            Err(DecodeErr::VarintOverflow)
+        );
+        assert_eq!(
+            Decoder::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]).varint(),
+            Ok(u64::MAX)
+        );
+        assert_eq!(
+            Decoder::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]).varint(),
+            Err(DecodeErr::VarintOverflow)
+        );
+        assert_eq!(
+            Decoder::new(&[0xff, 0xff, 0xff, 0xff, 0x0f]).bytes(),
+            Err(DecodeErr::UnexpectedEnd)
+        );
+        assert_eq!(
+            transaction::Output::from_bytes(&[1, 0xff, 0, 0, 0, 0, 0, 0, 0, 0]).err(),
+            Some(DecodeErr::InvalidUtf8)
+        );
+        assert_eq!(
+            Transaction::from_bytes(&[ENCODING_VERSION, 0xff, 0xff, 0xff, 0xff, 0x0f]).err(),
+            Some(DecodeErr::UnexpectedEnd)
+        );
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..1000 {
+            let mut fuzzed = bytes.clone();
+            for _ in 0..4 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let index = state as usize % fuzzed.len();
+                fuzzed[index] = (state >> 32) as u8;
+            }
+            if let Ok(decoded) = Block::from_bytes(&fuzzed) {
+                assert_eq!(decoded.to_bytes(), fuzzed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_duplicate_block() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let bits = 0x2000_ffff;
+        let mine = || {
+            let mut block = Block::new(
+                index,
+                timestamp,
+                Hash256::ZERO,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .update_with_block(mine())
+            .expect("Failed to add genesis block");
+
+        let block = mine();
+        let hash = block.hash;
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(DuplicateBlock { hash })
+        );
+    }
+
+    #[test]
+    fn test_error_excessive_coinbase_value() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let bits = 0x2000_ffff;
+        let mut blockchain = Blockchain::new();
+
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: blockchain.params().block_subsidy(index) + 1,
+                }],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let subsidy = blockchain.params().block_subsidy(index);
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(ExcessiveCoinbaseValue {
+                maximum: subsidy,
+                actual: subsidy + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_insufficient_input_value() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                },
+                user_a.sign(Transaction {
+                    inputs: vec![Input::new(OutPoint {
+                        txid: blockchain.blocks[0].transactions[0].hash(),
+                        index: 0,
+                    })],
+                    outputs: vec![transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins * 2,
+                    }],
+                    height: 0,
+                    extra_nonce: 0,
+                }),
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InsufficientInputValue {
+                tx: 1,
+                input_value: user_a_coins,
+                output_value: user_a_coins * 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_coinbase_transaction() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let prev_block_hash = Hash256::ZERO;
+        let bits = 0x2000_ffff;
+
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: Hash256::ZERO,
+                    index: 0,
+                })],
+                outputs: vec![],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let txid = block.transactions[0].hash();
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidCoinbaseTransaction { txid })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_coinbase_transaction_height() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let prev_block_hash = Hash256::ZERO;
+        let bits = 0x2000_ffff;
+
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: index + 1,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidCoinbaseTransactionHeight {
+                expected: index,
+                actual: index + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_coinbase_transaction_fee() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let user_b = Wallet::from_seed(b"Bob");
+        let user_b_coins = 7;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                    transaction::Output {
+                        to_addr: user_b.address(),
+                        value: user_b_coins,
+                    },
+                ],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let zero_value_transaction_vector = vec![transaction::Output {
+            to_addr: user_a.address(),
+            value: 0,
+        }];
+        let positive_value_transaction_vector = vec![Input::new(OutPoint {
+            txid: blockchain.blocks[0].transactions[0].hash(),
+            index: 0,
+        })];
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: zero_value_transaction_vector.clone(),
+                    height: index,
+                    extra_nonce: 0,
+                },
+                user_a.sign(Transaction {
+                    inputs: positive_value_transaction_vector.clone(),
+                    outputs: zero_value_transaction_vector.clone(),
+                    height: 0,
+                    extra_nonce: 0,
+                }),
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidCoinbaseTransactionFee {
+                minimum: user_a_coins,
+                actual: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_difficulty() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: index,
+                extra_nonce: 0,
+            }],
+            0x2100_ffff,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidDifficulty {
+                expected: bits,
+                actual: 0x2100_ffff,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_genesis_block_format() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let user_b = Wallet::from_seed(b"Bob");
+        let user_b_coins = 7;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                    transaction::Output {
+                        to_addr: user_b.address(),
+                        value: user_b_coins,
+                    },
+                ],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let prev_block_hash = genesis_block.hash;
+        genesis_block.header.prev_block_hash = prev_block_hash;
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(genesis_block),
+            Err(InvalidGenesisBlockFormat { prev_block_hash })
         );
     }
 
     #[test]
     fn test_error_invalid_hash() {
         let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let user_b = Wallet::from_seed(b"Bob");
+        let user_b_coins = 7;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                    transaction::Output {
+                        to_addr: user_b.address(),
+                        value: user_b_coins,
+                    },
+                ],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        block.header.bits = 0;
+
+        let (expected, actual) = (block.hash(), block.hash);
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidHash { expected, actual })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_hash_unmined() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let prev_block_hash = Hash256::ZERO;
+        let bits = 0x2000_ffff;
+
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(Block::new(index, timestamp, Hash256::ZERO, vec![], bits)),
+            Err(InvalidHash {
+                expected: Block::new(index, timestamp, Hash256::ZERO, vec![], bits).hash(),
+                actual: Hash256::ZERO,
+            })
+        );
+
+        block.header.bits = 0x0300_0001;
+        block.hash = block.hash();
+        let hash = block.hash;
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InsufficientProofOfWork {
+                hash,
+                bits: 0x0300_0001
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_input() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
         let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
+        let user_b = Wallet::from_seed(b"Bob");
         let user_b_coins = 7;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_addr: user_a.address(),
+                        value: user_a_coins,
+                    },
+                    transaction::Output {
+                        to_addr: user_b.address(),
+                        value: user_b_coins,
+                    },
+                ],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                },
+                user_a.sign(Transaction {
+                    inputs: vec![
+                        Input::new(OutPoint {
+                            txid: blockchain.blocks[0].transactions[0].hash(),
+                            index: 0,
+                        }),
+                        Input::new(OutPoint {
+                            txid: Hash256::ZERO,
+                            index: 0,
+                        }),
+                    ],
+                    outputs: vec![],
+                    height: 0,
+                    extra_nonce: 0,
+                }),
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let result = blockchain.update_with_block(block);
+        assert_eq!(
+            result,
+            Err(InvalidInput {
+                tx: 1,
+                input: 1,
+                outpoint: OutPoint {
+                    txid: Hash256::ZERO,
+                    index: 0,
+                },
+            })
+        );
+
+        let err: Box<dyn std::error::Error> = Box::new(result.unwrap_err());
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "transaction 1 input 1 spends missing or already spent output {}:0",
+                Hash256::ZERO
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_merkle_root() {
+        let index = 0;
+        let timestamp = TIMESTAMP;
+        let prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        genesis_block.transactions[0]
+            .outputs
+            .push(transaction::Output {
+                to_addr: user_a.address(),
+                value: 50,
+            });
+
+        let (expected, actual) = (
+            genesis_block.merkle_root(),
+            genesis_block.header.merkle_root,
+        );
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(genesis_block),
+            Err(InvalidMerkleRoot { expected, actual })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_scrypt_params() {
+        use hashable::{HashAlgorithm, ScryptParams};
+
+        for (log_n, r, p) in [
+            (70, 1, 1),
+            (64, 1, 1),
+            (10, 0, 1),
+            (10, 1, 0),
+            (10, 1 << 15, 1 << 15),
+        ] {
+            assert_eq!(ScryptParams::new(log_n, r, p), None);
+            assert_eq!(HashAlgorithm::scrypt(log_n, r, p), None);
+        }
+
+        let params = ScryptParams::new(10, 1, 1).unwrap();
+        assert_eq!((params.log_n(), params.r(), params.p()), (10, 1, 1));
+        assert_eq!(
+            HashAlgorithm::scrypt(10, 1, 1),
+            Some(HashAlgorithm::LITECOIN_SCRYPT)
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_side_branch() {
+        let timestamp = TIMESTAMP;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+        let mine = |index, prev_block_hash, to_addr, value| {
+            let mut block = Block::new(
+                index,
+                timestamp + index as u128,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![transaction::Output { to_addr, value }],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let genesis_block = mine(0, Hash256::ZERO, user_a.address(), 1);
+        let block_a1 = mine(1, genesis_block.hash, user_a.address(), 1);
+        let block_b1 = mine(
+            1,
+            genesis_block.hash,
+            user_b.address(),
+            transaction::MAX_MONEY,
+        );
+        let block_b2 = mine(2, block_b1.hash, user_b.address(), 1);
+        let (hash_a1, hash_b1) = (block_a1.hash, block_b1.hash);
+        let coinbase_a1 = OutPoint {
+            txid: block_a1.transactions[0].hash(),
+            index: 0,
+        };
+        let mut block_c1 = Block::new(
+            1,
+            timestamp + 1,
+            genesis_block.hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: 1,
+                    extra_nonce: 1,
+                },
+                user_a.sign(Transaction {
+                    inputs: vec![Input::new(coinbase_a1.clone())],
+                    outputs: vec![],
+                    height: 0,
+                    extra_nonce: 0,
+                }),
+            ],
+            bits,
+        );
+        block_c1.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+        blockchain
+            .update_with_block(block_a1)
+            .expect("Failed to add block");
+        let unspent_outputs = blockchain.unspent_outputs().clone();
+        let maximum = blockchain.params().block_subsidy(1);
+
+        assert_eq!(
+            blockchain.validate_block(&block_c1),
+            Err(InvalidInput {
+                tx: 1,
+                input: 0,
+                outpoint: coinbase_a1,
+            })
+        );
+        assert_eq!(
+            blockchain.validate_block(&block_b1),
+            Err(ExcessiveCoinbaseValue {
+                maximum,
+                actual: transaction::MAX_MONEY,
+            })
+        );
+        assert_eq!(
+            blockchain.update_with_block(block_b1),
+            Err(ExcessiveCoinbaseValue {
+                maximum,
+                actual: transaction::MAX_MONEY,
+            })
+        );
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
+        assert_eq!(blockchain.blocks.len(), 2);
+        assert_eq!(blockchain.blocks[1].hash, hash_a1);
+        assert!(blockchain.block(&hash_b1).is_none());
+        assert_eq!(
+            blockchain.update_with_block(block_b2),
+            Err(MismatchedPreviousHash {
+                prev_block_hash: hash_b1
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_signature() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let transaction = user_b.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: blockchain.blocks[0].transactions[0].hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_b.address(),
+                value: user_a_coins,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                },
+                transaction,
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidSignature { tx: 1, input: 0 })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_signature_tampered_output() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        index += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let mut transaction = user_a.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: blockchain.blocks[0].transactions[0].hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_a.address(),
+                value: user_a_coins,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        transaction.outputs[0].to_addr = user_b.address();
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                },
+                transaction,
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidSignature { tx: 1, input: 0 })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_transaction_height() {
+        let mut index = 0;
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_a_coins = 50;
+        let bits = 0x2000_ffff;
 
         let mut genesis_block = Block::new(
             index,
@@ -420,124 +3109,378 @@ mod tests {
             prev_block_hash,
             vec![Transaction {
                 inputs: vec![],
-                outputs: vec![
-                    transaction::Output {
-                        to_addr: user_a.clone(),
-                        value: user_a_coins,
-                    },
-                    transaction::Output {
-                        to_addr: user_b.clone(),
-                        value: user_b_coins,
-                    },
-                ],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: user_a_coins,
+                }],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
 
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
 
         index += 1;
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        let transaction = user_a.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: blockchain.blocks[0].transactions[0].hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_a.address(),
+                value: user_a_coins,
+            }],
+            height: index,
+            extra_nonce: 0,
+        });
         let mut block = Block::new(
             index,
             timestamp,
             prev_block_hash,
+            vec![
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                },
+                transaction.clone(),
+            ],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(InvalidTransactionHeight {
+                tx: 1,
+                actual: index
+            })
+        );
+        assert!(matches!(
+            mempool::Mempool::new(100_000).add(&blockchain, transaction),
+            Err(mempool::MempoolErr::InvalidTransaction(
+                InvalidTransactionHeight { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_error_mempool() {
+        use mempool::MempoolErr;
+
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
             vec![Transaction {
                 inputs: vec![],
-                outputs: vec![],
+                outputs: (0..2)
+                    .map(|_| transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 10,
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
             }],
-            difficulty,
+            0x2000_ffff,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let coinbase_txid = genesis_block.transactions[0].hash();
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = |signer: &Wallet, index, value, extra_nonce| {
+            signer.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: coinbase_txid,
+                    index,
+                })],
+                outputs: vec![transaction::Output {
+                    to_addr: user_b.address(),
+                    value,
+                }],
+                height: 0,
+                extra_nonce,
+            })
+        };
+        let size = spend(&user_a, 0, 0, 0).to_bytes().len();
+        let mut mempool = mempool::Mempool::new(size);
+
+        assert_eq!(
+            mempool.add(
+                &blockchain,
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: 1,
+                    extra_nonce: 0,
+                }
+            ),
+            Err(MempoolErr::CoinbaseTransaction)
+        );
+        assert_eq!(
+            mempool.add(&blockchain, spend(&user_b, 0, 9, 0)),
+            Err(MempoolErr::InvalidTransaction(InvalidSignature {
+                tx: 1,
+                input: 0
+            }))
+        );
+        assert_eq!(
+            mempool.add(&blockchain, spend(&user_a, 0, 11, 0)),
+            Err(MempoolErr::InvalidTransaction(InsufficientInputValue {
+                tx: 1,
+                input_value: 10,
+                output_value: 11,
+            }))
         );
-        block.mine();
-        block.difficulty = 0;
+        assert_eq!(
+            mempool.add(&blockchain, spend(&user_a, 2, 9, 0)),
+            Err(MempoolErr::InvalidTransaction(InvalidInput {
+                tx: 1,
+                input: 0,
+                outpoint: OutPoint {
+                    txid: coinbase_txid,
+                    index: 2,
+                },
+            }))
+        );
+
+        mempool
+            .add(&blockchain, spend(&user_a, 0, 8, 1))
+            .expect("Failed to add transaction");
+        assert_eq!(
+            mempool.add(&blockchain, spend(&user_a, 0, 8, 1)),
+            Err(MempoolErr::DuplicateTransaction)
+        );
+        assert_eq!(
+            MempoolErr::InvalidTransaction(InvalidSignature { tx: 1, input: 0 }).to_string(),
+            "transaction is invalid: transaction 1 input 0 has an invalid key or signature"
+        );
+        assert_eq!(
+            mempool.add(&blockchain, spend(&user_a, 0, 7, 1)),
+            Err(MempoolErr::DoubleSpend)
+        );
+        assert_eq!(
+            mempool.add(&blockchain, spend(&user_a, 1, 9, 1)),
+            Err(MempoolErr::FeeRateTooLow)
+        );
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool.size(), size);
 
-        assert_eq!(blockchain.update_with_block(block), Err(InvalidHash));
+        mempool
+            .add(&blockchain, spend(&user_a, 1, 7, 1))
+            .expect("Failed to add transaction");
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool.entries()[0].fee, 3);
     }
 
     #[test]
-    fn test_error_invalid_input() {
-        let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
-        let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
-        let user_b_coins = 7;
-        let user_c = "Nobody".to_owned();
-        let user_c_coins = 363893;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    fn test_error_mempool_eviction() {
+        use mempool::MempoolErr;
+
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
 
         let mut genesis_block = Block::new(
-            index,
-            timestamp,
-            prev_block_hash,
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
             vec![Transaction {
                 inputs: vec![],
-                outputs: vec![
-                    transaction::Output {
-                        to_addr: user_a.clone(),
-                        value: user_a_coins,
-                    },
-                    transaction::Output {
-                        to_addr: user_b.clone(),
-                        value: user_b_coins,
-                    },
-                ],
+                outputs: (0..3)
+                    .map(|_| transaction::Output {
+                        to_addr: user_a.address(),
+                        value: 30,
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
             }],
-            difficulty,
+            0x2000_ffff,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        let coinbase_txid = genesis_block.transactions[0].hash();
 
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
 
-        index += 1;
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
+        let spend = |index, values: &[u64]| {
+            user_a.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: coinbase_txid,
+                    index,
+                })],
+                outputs: values
+                    .iter()
+                    .map(|&value| transaction::Output {
+                        to_addr: user_b.address(),
+                        value,
+                    })
+                    .collect(),
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+        let (t0, t1, t2) = (spend(0, &[29]), spend(1, &[10]), spend(2, &[5; 5]));
+        let (txid_0, txid_1) = (t0.hash(), t1.hash());
+        let size = t0.to_bytes().len() + t1.to_bytes().len();
+        assert!(t0.to_bytes().len() < t2.to_bytes().len());
+
+        let mut mempool = mempool::Mempool::new(size);
+        for transaction in [t0, t1] {
+            mempool
+                .add(&blockchain, transaction)
+                .expect("Failed to add transaction");
+        }
+
+        assert_eq!(mempool.add(&blockchain, t2), Err(MempoolErr::FeeRateTooLow));
+        assert_eq!(mempool.len(), 2);
+        assert_eq!(mempool.size(), size);
+        assert!(mempool.contains(&txid_0));
+        assert!(mempool.contains(&txid_1));
+    }
+
+    #[test]
+    fn test_error_mining_cancelled() {
+        let mut block = Block::new(0, TIMESTAMP, Hash256::ZERO, vec![], 0x0300_0001);
+        let miner = miner::Miner::new(4);
+        let cancel = miner.cancel.clone();
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            cancel.cancel();
+        });
+        let result = miner.mine(&mut block);
+        canceller.join().unwrap();
+
+        match result {
+            Err(miner::MiningErr::Cancelled(stats)) => assert!(0 < stats.hashes),
+            result => panic!("Expected cancellation, got {result:?}"),
+        }
+        assert_eq!(block.hash, Hash256::ZERO);
+
+        assert!(matches!(
+            miner.mine(&mut block),
+            Err(miner::MiningErr::Cancelled(_))
+        ));
+    }
+
+    #[test]
+    fn test_error_mining_exhausted() {
+        let mut block = Block::new(0, TIMESTAMP, Hash256::ZERO, vec![], 0x0300_0001);
+        let miner = miner::Miner {
+            nonces: 10..1010,
+            ..miner::Miner::new(3)
+        };
+
+        match miner.mine(&mut block) {
+            Err(miner::MiningErr::Exhausted(stats)) => {
+                assert_eq!(stats.hashes, 1000);
+                assert_eq!(stats.threads, 3);
+            }
+            result => panic!("Expected exhaustion, got {result:?}"),
+        }
+        assert_eq!(block.hash, Hash256::ZERO);
+
+        let timestamp = TIMESTAMP;
         let mut block = Block::new(
-            index,
+            0,
             timestamp,
-            prev_block_hash,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: 0,
+                extra_nonce: u64::MAX - 2,
+            }],
+            0x0300_0001,
+        );
+        let miner = miner::Miner {
+            nonces: 0..10,
+            max_timestamp: Some(timestamp + 1),
+            clock: Arc::new(clock::ManualClock::new(timestamp)),
+            ..miner::Miner::new(2)
+        };
+        let mut events = vec![];
+
+        match miner.mine_with(&mut block, |event| events.push(format!("{event:?}"))) {
+            Err(miner::MiningErr::Exhausted(stats)) => assert_eq!(stats.hashes, 40),
+            result => panic!("Expected exhaustion, got {result:?}"),
+        }
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.contains("Rolled"))
+                .collect::<Vec<_>>(),
             vec![
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![
-                        blockchain.blocks[0].transactions[0].outputs[0].clone(),
-                        transaction::Output {
-                            to_addr: user_c.clone(),
-                            value: user_c_coins,
-                        },
-                    ],
-                    outputs: vec![],
-                },
-            ],
-            difficulty,
+                &format!("TimestampRolled({})", timestamp + 1),
+                &format!("ExtraNonceRolled({})", u64::MAX - 1),
+                &format!("ExtraNonceRolled({})", u64::MAX),
+            ]
         );
-        block.mine();
+        assert_eq!(block.transactions[0].extra_nonce, u64::MAX);
+    }
+
+    #[test]
+    fn test_error_mining_invalid_target() {
+        let miner = miner::Miner {
+            nonces: 0..1_000_000_000,
+            ..miner::Miner::new(2)
+        };
+        let mut events = vec![];
+
+        for bits in [0, 0x0100_0000, 0x0180_0001, 0x2300_ffff] {
+            let mut block = Block::new(0, TIMESTAMP, Hash256::ZERO, vec![], bits);
 
-        assert_eq!(blockchain.update_with_block(block), Err(InvalidInput));
+            assert_eq!(
+                miner.mine_with(&mut block, |event| events.push(format!("{event:?}"))),
+                Err(miner::MiningErr::InvalidTarget(bits))
+            );
+            assert_eq!(block.hash, Hash256::ZERO);
+            assert_eq!(
+                block.mine_sha256(),
+                Err(miner::MiningErr::InvalidTarget(bits))
+            );
+        }
+        assert!(events.is_empty());
     }
 
     #[test]
     fn test_error_mismatched_index() {
         let index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
         let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
+        let user_b = Wallet::from_seed(b"Bob");
         let user_b_coins = 7;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+        let bits = 0x2000_ffff;
 
         let mut genesis_block = Block::new(
             index,
@@ -547,26 +3490,28 @@ mod tests {
                 inputs: vec![],
                 outputs: vec![
                     transaction::Output {
-                        to_addr: user_a.clone(),
+                        to_addr: user_a.address(),
                         value: user_a_coins,
                     },
                     transaction::Output {
-                        to_addr: user_b.clone(),
+                        to_addr: user_b.address(),
                         value: user_b_coins,
                     },
                 ],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
 
         let mut blockchain = Blockchain::new();
         blockchain
             .update_with_block(genesis_block)
             .expect("Failed to add genesis block");
 
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
         let mut block = Block::new(
             index,
             timestamp,
@@ -574,23 +3519,31 @@ mod tests {
             vec![Transaction {
                 inputs: vec![],
                 outputs: vec![],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(MismatchedIndex {
+                expected: index + 1,
+                actual: index,
+            })
         );
-        block.mine();
-        assert_eq!(blockchain.update_with_block(block), Err(MismatchedIndex));
     }
 
     #[test]
     fn test_error_mismatched_previous_hash() {
         let mut index = 0;
-        let mut timestamp = now();
-        let mut prev_block_hash = vec![0; 32];
-        let user_a = "Alice".to_owned();
+        let mut timestamp = TIMESTAMP;
+        let mut prev_block_hash = Hash256::ZERO;
+        let user_a = Wallet::from_seed(b"Alice");
         let user_a_coins = 50;
-        let user_b = "Bob".to_owned();
+        let user_b = Wallet::from_seed(b"Bob");
         let user_b_coins = 7;
-        let difficulty = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+        let bits = 0x2000_ffff;
 
         let mut genesis_block = Block::new(
             index,
@@ -600,18 +3553,20 @@ mod tests {
                 inputs: vec![],
                 outputs: vec![
                     transaction::Output {
-                        to_addr: user_a.clone(),
+                        to_addr: user_a.address(),
                         value: user_a_coins,
                     },
                     transaction::Output {
-                        to_addr: user_b.clone(),
+                        to_addr: user_b.address(),
                         value: user_b_coins,
                     },
                 ],
+                height: index,
+                extra_nonce: 0,
             }],
-            difficulty,
+            bits,
         );
-        genesis_block.mine();
+        genesis_block.mine_sha256().expect("Failed to mine block");
 
         let mut blockchain = Blockchain::new();
         blockchain
@@ -619,9 +3574,9 @@ mod tests {
             .expect("Failed to add genesis block");
 
         index += 1;
-        timestamp = now();
-        prev_block_hash = blockchain.blocks.last().unwrap().hash.clone();
-        prev_block_hash[0] += 1;
+        timestamp += 1;
+        prev_block_hash = blockchain.blocks.last().unwrap().hash;
+        prev_block_hash.0[0] ^= 1;
         let mut block = Block::new(
             index,
             timestamp,
@@ -629,14 +3584,444 @@ mod tests {
             vec![Transaction {
                 inputs: vec![],
                 outputs: vec![],
+                height: index,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(MismatchedPreviousHash { prev_block_hash })
+        );
+    }
+
+    #[test]
+    fn test_error_misplaced_coinbase_transaction() {
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![],
+            height: 0,
+            extra_nonce: 0,
+        };
+        let mut block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![coinbase.clone(), coinbase],
+            0x2000_ffff,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        assert_eq!(
+            block.check_sha256(),
+            Err(MisplacedCoinbaseTransaction { tx: 1 })
+        );
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(MisplacedCoinbaseTransaction { tx: 1 })
+        );
+    }
+
+    #[test]
+    fn test_error_oversized_block() {
+        let mut block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: "0".repeat(block::MAX_BLOCK_SIZE),
+                    value: 0,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            0x2000_ffff,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let size = block.to_bytes().len();
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.update_with_block(block),
+            Err(OversizedBlock {
+                maximum: block::MAX_BLOCK_SIZE,
+                actual: size,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_premature_coinbase_spend() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 2,
+            ..params::Params::default()
+        });
+        let mut mempool = Mempool::new(100_000);
+        let template = BlockTemplate::new(user_a.address());
+
+        let mut genesis_block = template.build(&blockchain, &mempool);
+        genesis_block.mine_sha256().expect("Failed to mine block");
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let spend = user_a.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: blockchain.blocks[0].transactions[0].hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_b.address(),
+                value: 90,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let premature = |tx, height| PrematureCoinbaseSpend {
+            tx,
+            input: 0,
+            mature_height: 3,
+            height,
+        };
+
+        for height in 1..3 {
+            assert_eq!(
+                mempool.add(&blockchain, spend.clone()),
+                Err(mempool::MempoolErr::InvalidTransaction(premature(
+                    1, height
+                )))
+            );
+
+            let mut block = template.build(&blockchain, &[spend.clone()][..]);
+            assert_eq!(block.transactions.len(), 1);
+            block.transactions[0].outputs[0].value += 10;
+            block.transactions.push(spend.clone());
+            block.header.merkle_root = block.merkle_root();
+            block.mine_sha256().expect("Failed to mine block");
+            assert_eq!(
+                blockchain.update_with_block(block),
+                Err(premature(1, height))
+            );
+
+            let mut block = template.build(&blockchain, &mempool);
+            block.mine_sha256().expect("Failed to mine block");
+            blockchain
+                .update_with_block(block)
+                .expect("Failed to add block");
+        }
+
+        mempool
+            .add(&blockchain, spend.clone())
+            .expect("Failed to add transaction");
+        let mut block = template.build(&blockchain, &mempool);
+        assert_eq!(block.transactions[1..], [spend]);
+        block.mine_sha256().expect("Failed to mine block");
+        blockchain
+            .update_with_block(block)
+            .expect("Failed to add block");
+        assert_eq!(
+            premature(0, 1).to_string(),
+            "transaction 0 input 0 spends a coinbase output at height 1 before its maturity at 3"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_error_storage_failure() {
+        let mut block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            0x2000_ffff,
+        );
+        block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::open("/dev/full").expect("Failed to open blockchain");
+        assert!(matches!(
+            blockchain.update_with_block(block),
+            Err(StorageFailure(_))
+        ));
+        assert!(blockchain.blocks.is_empty());
+        assert!(blockchain.unspent_outputs().is_empty());
+    }
+
+    #[test]
+    fn test_error_timestamp_too_early() {
+        let timestamp = TIMESTAMP - 1_000;
+        let bits = 0x2000_ffff;
+        let mine = |index, timestamp, prev_block_hash| {
+            let mut block = Block::new(
+                index,
+                timestamp,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let mut blockchain = Blockchain::new();
+        for offset in [0, 10, 20] {
+            let index = blockchain.blocks.len() as u32;
+            let prev_block_hash = blockchain
+                .blocks
+                .last()
+                .map_or(Hash256::ZERO, |tip| tip.hash);
+            blockchain
+                .update_with_block(mine(index, timestamp + offset, prev_block_hash))
+                .expect("Failed to add block");
+        }
+        assert_eq!(blockchain.median_time_past(), timestamp + 10);
+
+        let prev_block_hash = blockchain.blocks[2].hash;
+        assert_eq!(
+            blockchain.update_with_block(mine(3, timestamp + 10, prev_block_hash)),
+            Err(TimestampTooEarly {
+                median_time_past: timestamp + 10,
+                actual: timestamp + 10,
+            })
+        );
+        blockchain
+            .update_with_block(mine(3, timestamp + 15, prev_block_hash))
+            .expect("Failed to add block");
+        assert_eq!(blockchain.median_time_past(), timestamp + 15);
+    }
+
+    #[test]
+    fn test_error_timestamp_too_late() {
+        let clock = clock::ManualClock::new(1_000_000);
+        let bits = 0x2000_ffff;
+        let mine = |index, timestamp, prev_block_hash| {
+            let mut block = Block::new(
+                index,
+                timestamp,
+                prev_block_hash,
+                vec![Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    height: index,
+                    extra_nonce: 0,
+                }],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            block
+        };
+
+        let mut blockchain = Blockchain::new();
+        blockchain.set_clock(clock.clone());
+        let maximum = clock.now() + blockchain.params().max_future_drift;
+        blockchain
+            .update_with_block(mine(0, maximum, Hash256::ZERO))
+            .expect("Failed to add genesis block");
+
+        let block = mine(1, maximum + 1, blockchain.blocks[0].hash);
+        assert_eq!(
+            blockchain.validate_block(&block),
+            Err(TimestampTooLate {
+                maximum,
+                actual: maximum + 1,
+            })
+        );
+        clock.advance(1);
+        assert_eq!(blockchain.validate_block(&block), Ok(()));
+        assert_eq!(
+            BlockTemplate::new(String::new())
+                .build(&blockchain, &Mempool::new(0))
+                .header
+                .timestamp,
+            maximum + 1
+        );
+    }
+
+    #[test]
+    fn test_error_unordered_transactions() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let user_b = Wallet::from_seed(b"Bob");
+        let bits = 0x2000_ffff;
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_addr: user_a.address(),
+                    value: 50,
+                }],
+                height: 0,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let a_to_b = user_a.sign(Transaction {
+            inputs: vec![Input::new(OutPoint {
+                txid: blockchain.blocks[0].transactions[0].hash(),
+                index: 0,
+            })],
+            outputs: vec![transaction::Output {
+                to_addr: user_b.address(),
+                value: 50,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let later_outpoint = OutPoint {
+            txid: a_to_b.hash(),
+            index: 0,
+        };
+        let b_to_a = user_b.sign(Transaction {
+            inputs: vec![Input::new(later_outpoint.clone())],
+            outputs: vec![transaction::Output {
+                to_addr: user_a.address(),
+                value: 50,
+            }],
+            height: 0,
+            extra_nonce: 0,
+        });
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![transaction::Output {
+                to_addr: user_b.address(),
+                value: blockchain.params().block_subsidy(1),
             }],
-            difficulty,
+            height: 1,
+            extra_nonce: 0,
+        };
+        let mut block = Block::new(
+            1,
+            blockchain.blocks[0].header.timestamp + 1,
+            blockchain.blocks[0].hash,
+            vec![coinbase, b_to_a, a_to_b],
+            bits,
         );
-        block.mine();
+        block.mine_sha256().expect("Failed to mine block");
 
+        let unspent_outputs = blockchain.unspent_outputs().clone();
         assert_eq!(
             blockchain.update_with_block(block),
-            Err(MismatchedPreviousHash)
+            Err(InvalidInput {
+                tx: 1,
+                input: 0,
+                outpoint: later_outpoint,
+            })
+        );
+        assert_eq!(blockchain.unspent_outputs(), &unspent_outputs);
+        assert_eq!(blockchain.blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_error_value_out_of_range() {
+        let user_a = Wallet::from_seed(b"Alice");
+        let bits = 0x2000_ffff;
+        let outputs = |values: &[u64]| {
+            values
+                .iter()
+                .map(|&value| transaction::Output {
+                    to_addr: user_a.address(),
+                    value,
+                })
+                .collect::<Vec<transaction::Output>>()
+        };
+
+        let mut genesis_block = Block::new(
+            0,
+            TIMESTAMP,
+            Hash256::ZERO,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: outputs(&[50]),
+                height: 0,
+                extra_nonce: 0,
+            }],
+            bits,
+        );
+        genesis_block.mine_sha256().expect("Failed to mine block");
+
+        let mut blockchain = Blockchain::with_params(params::Params {
+            coinbase_maturity: 0,
+            ..params::Params::default()
+        });
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add genesis block");
+
+        let genesis_txid = blockchain.blocks[0].transactions[0].hash();
+        let spend = |values: &[u64]| {
+            user_a.sign(Transaction {
+                inputs: vec![Input::new(OutPoint {
+                    txid: genesis_txid,
+                    index: 0,
+                })],
+                outputs: outputs(values),
+                height: 0,
+                extra_nonce: 0,
+            })
+        };
+        let max_money = transaction::MAX_MONEY;
+        assert_eq!(spend(&[max_money]).output_value(), Some(max_money));
+        assert_eq!(spend(&[max_money + 1]).output_value(), None);
+        assert_eq!(spend(&[max_money, 1]).output_value(), None);
+        assert_eq!(spend(&[u64::MAX, 1]).output_value(), None);
+
+        for values in [[max_money + 1, 0], [max_money, max_money]] {
+            let coinbase = Transaction {
+                inputs: vec![],
+                outputs: outputs(&[blockchain.params().block_subsidy(1)]),
+                height: 1,
+                extra_nonce: 0,
+            };
+            let mut block = Block::new(
+                1,
+                blockchain.blocks[0].header.timestamp + 1,
+                blockchain.blocks[0].hash,
+                vec![coinbase, spend(&values)],
+                bits,
+            );
+            block.mine_sha256().expect("Failed to mine block");
+            assert_eq!(
+                blockchain.update_with_block(block),
+                Err(ValueOutOfRange { tx: 1 })
+            );
+        }
+
+        let mut mempool = Mempool::new(100_000);
+        assert_eq!(
+            mempool.add(&blockchain, spend(&[max_money, max_money])),
+            Err(mempool::MempoolErr::InvalidTransaction(ValueOutOfRange {
+                tx: 1
+            }))
+        );
+        assert_eq!(
+            ValueOutOfRange { tx: 1 }.to_string(),
+            format!("transaction 1 has a value outside the range 0..={max_money}")
         );
     }
 }
@@ -0,0 +1,293 @@
+use {
+    super::{
+        block::Block,
+        blockchain::{check_transaction, BlockValidationErr, Blockchain, ChainEvent},
+        encoding::Encodable,
+        hashable::Hashable,
+        transaction::{OutPoint, Transaction},
+        types::Hash256,
+    },
+    std::{
+        cmp::Ordering,
+        collections::HashMap,
+        fmt::{self, Display, Formatter},
+        iter,
+    },
+};
+
+pub const DEFAULT_EXPIRY: u128 = 14 * 24 * 60 * 60 * 1000;
+
+#[derive(Debug, PartialEq)]
+pub enum MempoolErr {
+    CoinbaseTransaction,
+    DoubleSpend,
+    DuplicateTransaction,
+    FeeRateTooLow,
+    InvalidTransaction(BlockValidationErr),
+}
+
+impl Display for MempoolErr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            MempoolErr::CoinbaseTransaction => {
+                write!(f, "coinbase transactions cannot enter the pool")
+            }
+            MempoolErr::DoubleSpend => {
+                write!(f, "transaction spends an output already spent in the pool")
+            }
+            MempoolErr::DuplicateTransaction => write!(f, "transaction is already in the pool"),
+            MempoolErr::FeeRateTooLow => {
+                write!(f, "fee rate is too low for the pool to make room")
+            }
+            MempoolErr::InvalidTransaction(err) => write!(f, "transaction is invalid: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MempoolErr {}
+
+#[derive(Debug)]
+pub struct MempoolEntry {
+    pub txid: Hash256,
+    pub transaction: Transaction,
+    pub fee: u64,
+    pub size: usize,
+    pub time: u128,
+}
+
+impl MempoolEntry {
+    pub fn fee_rate(&self) -> f64 {
+        self.fee as f64 / self.size.max(1) as f64
+    }
+
+    pub fn cmp_fee_rate(&self, other: &MempoolEntry) -> Ordering {
+        (self.fee as u128 * other.size as u128)
+            .cmp(&(other.fee as u128 * self.size as u128))
+            .then_with(|| other.txid.cmp(&self.txid))
+    }
+}
+
+pub struct Mempool {
+    max_size: usize,
+    size: usize,
+    expiry: u128,
+    entries: HashMap<Hash256, MempoolEntry>,
+    spent: HashMap<OutPoint, Hash256>,
+}
+
+impl Mempool {
+    pub fn new(max_size: usize) -> Self {
+        Mempool {
+            max_size,
+            size: 0,
+            expiry: DEFAULT_EXPIRY,
+            entries: HashMap::new(),
+            spent: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn expiry(&self) -> u128 {
+        self.expiry
+    }
+
+    pub fn set_expiry(&mut self, expiry: u128) {
+        self.expiry = expiry;
+    }
+
+    pub fn contains(&self, txid: &Hash256) -> bool {
+        self.entries.contains_key(txid)
+    }
+
+    pub fn get(&self, txid: &Hash256) -> Option<&MempoolEntry> {
+        self.entries.get(txid)
+    }
+
+    pub fn spender(&self, outpoint: &OutPoint) -> Option<&Hash256> {
+        self.spent.get(outpoint)
+    }
+
+    pub fn entries(&self) -> Vec<&MempoolEntry> {
+        let mut entries = self.entries.values().collect::<Vec<&MempoolEntry>>();
+        entries.sort_by(|a, b| b.cmp_fee_rate(a));
+
+        entries
+    }
+
+    pub fn add(
+        &mut self,
+        blockchain: &Blockchain,
+        transaction: Transaction,
+    ) -> Result<Hash256, MempoolErr> {
+        if transaction.is_coinbase() {
+            return Err(MempoolErr::CoinbaseTransaction);
+        }
+
+        let now = blockchain.now();
+        self.expire(now);
+
+        let txid = transaction.hash();
+        if self.entries.contains_key(&txid) {
+            return Err(MempoolErr::DuplicateTransaction);
+        }
+        if transaction
+            .inputs
+            .iter()
+            .any(|input| self.spent.contains_key(&input.outpoint))
+        {
+            return Err(MempoolErr::DoubleSpend);
+        }
+
+        let height = blockchain.blocks.len() as u32;
+        let fee = check_transaction(
+            self.entries.len() + 1,
+            &transaction,
+            height,
+            blockchain.params().coinbase_maturity,
+            |outpoint| blockchain.unspent_output(outpoint),
+        )
+        .map_err(MempoolErr::InvalidTransaction)?;
+
+        let size = transaction.to_bytes().len();
+        let entry = MempoolEntry {
+            txid,
+            transaction,
+            fee,
+            size,
+            time: now,
+        };
+
+        let mut pool_size = self.size + size;
+        let mut evicted = vec![];
+        if self.max_size < pool_size {
+            let mut entries = self
+                .entries
+                .values()
+                .chain(iter::once(&entry))
+                .collect::<Vec<&MempoolEntry>>();
+            entries.sort_by(|a, b| a.cmp_fee_rate(b));
+
+            for cheapest in entries {
+                if pool_size <= self.max_size {
+                    break;
+                } else if cheapest.txid == txid {
+                    return Err(MempoolErr::FeeRateTooLow);
+                }
+
+                pool_size -= cheapest.size;
+                evicted.push(cheapest.txid);
+            }
+        }
+
+        for txid in &evicted {
+            self.remove(txid);
+        }
+        self.insert(entry);
+
+        Ok(txid)
+    }
+
+    pub fn remove(&mut self, txid: &Hash256) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(txid)?;
+
+        self.size -= entry.size;
+        for input in &entry.transaction.inputs {
+            self.spent.remove(&input.outpoint);
+        }
+
+        Some(entry)
+    }
+
+    pub fn remove_block(&mut self, block: &Block) {
+        for transaction in &block.transactions {
+            self.remove(&transaction.hash());
+
+            for input in &transaction.inputs {
+                if let Some(txid) = self.spent.get(&input.outpoint).cloned() {
+                    self.remove(&txid);
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, blockchain: &Blockchain, events: &[ChainEvent]) {
+        self.expire(blockchain.now());
+
+        for event in events {
+            if let ChainEvent::BlockConnected(hash) = event {
+                if let Some(block) = blockchain.block(hash) {
+                    self.remove_block(block);
+                }
+            }
+        }
+
+        if events
+            .iter()
+            .any(|event| matches!(event, ChainEvent::BlockDisconnected(_)))
+        {
+            self.revalidate(blockchain);
+        }
+    }
+
+    pub fn expire(&mut self, now: u128) -> usize {
+        let expired = self
+            .entries
+            .values()
+            .filter(|entry| self.expiry <= now.saturating_sub(entry.time))
+            .map(|entry| entry.txid)
+            .collect::<Vec<Hash256>>();
+
+        for txid in &expired {
+            self.remove(txid);
+        }
+
+        expired.len()
+    }
+
+    pub fn revalidate(&mut self, blockchain: &Blockchain) {
+        let height = blockchain.blocks.len() as u32;
+        let invalid = self
+            .entries()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, entry)| {
+                check_transaction(
+                    i + 1,
+                    &entry.transaction,
+                    height,
+                    blockchain.params().coinbase_maturity,
+                    |outpoint| blockchain.unspent_output(outpoint),
+                )
+                .is_err()
+            })
+            .map(|(_, entry)| entry.txid)
+            .collect::<Vec<Hash256>>();
+
+        for txid in invalid {
+            self.remove(&txid);
+        }
+    }
+
+    fn insert(&mut self, entry: MempoolEntry) {
+        self.size += entry.size;
+        for input in &entry.transaction.inputs {
+            self.spent.insert(input.outpoint.clone(), entry.txid);
+        }
+        self.entries.insert(entry.txid, entry);
+    }
+}
@@ -0,0 +1,124 @@
+use super::{
+    encoding::{encode_list, Decodable, DecodeErr, Decoder, Encodable, ENCODING_VERSION},
+    types::Hash256,
+    utility::u32_bytes,
+};
+
+pub fn merkle_parent(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut bytes = vec![0x01];
+
+    bytes.extend(left.as_bytes());
+    bytes.extend(right.as_bytes());
+
+    Hash256::sha256(&bytes)
+}
+
+fn merkle_level(level: &[Hash256]) -> Vec<Hash256> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => merkle_parent(left, right),
+            [odd] => *odd,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+pub fn merkle_root(txids: &[Hash256]) -> Hash256 {
+    if txids.is_empty() {
+        return Hash256::ZERO;
+    }
+
+    let mut level = txids.to_vec();
+
+    while 1 < level.len() {
+        level = merkle_level(&level);
+    }
+
+    level.remove(0)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleProof {
+    pub index: u32,
+    pub leaf_count: u32,
+    pub branch: Vec<Hash256>,
+}
+
+impl MerkleProof {
+    pub fn new(txids: &[Hash256], index: usize) -> Option<Self> {
+        if txids.len() <= index {
+            return None;
+        }
+
+        let mut proof = MerkleProof {
+            index: index as u32,
+            leaf_count: txids.len() as u32,
+            branch: vec![],
+        };
+        let mut level = txids.to_vec();
+        let mut position = index;
+
+        while 1 < level.len() {
+            if let Some(sibling) = level.get(position ^ 1) {
+                proof.branch.push(*sibling);
+            }
+
+            level = merkle_level(&level);
+            position /= 2;
+        }
+
+        Some(proof)
+    }
+
+    pub fn verify(&self, txid: &Hash256, merkle_root: &Hash256) -> bool {
+        if self.leaf_count <= self.index {
+            return false;
+        }
+
+        let mut hash = *txid;
+        let mut branch = self.branch.iter();
+        let mut position = self.index;
+        let mut width = self.leaf_count;
+
+        while 1 < width {
+            if 1 == position % 2 {
+                let Some(sibling) = branch.next() else {
+                    return false;
+                };
+                hash = merkle_parent(sibling, &hash);
+            } else if position + 1 < width {
+                let Some(sibling) = branch.next() else {
+                    return false;
+                };
+                hash = merkle_parent(&hash, sibling);
+            }
+
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        branch.next().is_none() && &hash == merkle_root
+    }
+}
+
+impl Encodable for MerkleProof {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.push(ENCODING_VERSION);
+        bytes.extend(&u32_bytes(&self.index));
+        bytes.extend(&u32_bytes(&self.leaf_count));
+        encode_list(bytes, &self.branch);
+    }
+}
+
+impl Decodable for MerkleProof {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        decoder.version()?;
+
+        Ok(MerkleProof {
+            index: decoder.u32()?,
+            leaf_count: decoder.u32()?,
+            branch: decoder.list()?,
+        })
+    }
+}
@@ -0,0 +1,233 @@
+use {
+    super::{
+        block::{Block, BlockHeader},
+        blockchain::Blockchain,
+        clock::{Clock, SystemClock},
+        hashable::{HashAlgorithm, Hashable},
+        params::Params,
+        target::{self, U256},
+    },
+    std::{
+        fmt::{self, Display, Formatter},
+        ops::Range,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MiningStats {
+    pub hashes: u64,
+    pub elapsed: Duration,
+    pub threads: usize,
+}
+
+impl MiningStats {
+    pub fn hash_rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+
+        if 0.0 < seconds {
+            self.hashes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Display for MiningStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} hashes in {:?} on {} threads ({:.0} H/s)",
+            self.hashes,
+            self.elapsed,
+            self.threads,
+            self.hash_rate(),
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MiningErr {
+    Cancelled(MiningStats),
+    Exhausted(MiningStats),
+    InvalidTarget(u32),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MiningEvent {
+    NoncesExhausted(MiningStats),
+    TimestampRolled(u128),
+    ExtraNonceRolled(u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct Miner {
+    pub threads: usize,
+    pub nonces: Range<u64>,
+    pub max_timestamp: Option<u128>,
+    pub cancel: CancelToken,
+    pub clock: Arc<dyn Clock>,
+    pub block_hash: HashAlgorithm,
+    pub pow_hash: HashAlgorithm,
+}
+
+impl Default for Miner {
+    fn default() -> Self {
+        Miner {
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            nonces: 0..u64::MAX,
+            max_timestamp: None,
+            cancel: CancelToken::new(),
+            clock: Arc::new(SystemClock),
+            block_hash: HashAlgorithm::Sha256,
+            pow_hash: HashAlgorithm::Sha256,
+        }
+    }
+}
+
+impl Miner {
+    pub fn new(threads: usize) -> Self {
+        Miner {
+            threads,
+            ..Miner::default()
+        }
+    }
+
+    pub fn for_params(params: &Params) -> Self {
+        Miner {
+            block_hash: params.block_hash,
+            pow_hash: params.pow_hash,
+            ..Miner::default()
+        }
+    }
+
+    pub fn for_blockchain(blockchain: &Blockchain) -> Self {
+        let max_timestamp = blockchain
+            .now()
+            .saturating_add(blockchain.params().max_future_drift);
+
+        Miner {
+            max_timestamp: Some(max_timestamp),
+            clock: blockchain.clock(),
+            ..Miner::for_params(blockchain.params())
+        }
+    }
+
+    pub fn mine(&self, block: &mut Block) -> Result<MiningStats, MiningErr> {
+        self.mine_with(block, |_| {})
+    }
+
+    pub fn mine_with<F>(&self, block: &mut Block, mut on_event: F) -> Result<MiningStats, MiningErr>
+    where
+        F: FnMut(&MiningEvent),
+    {
+        let started = Instant::now();
+        let threads = self.threads.max(1);
+        let target =
+            target::target(block.header.bits).ok_or(MiningErr::InvalidTarget(block.header.bits))?;
+        let mut hashes = 0;
+
+        loop {
+            let (nonce, round_hashes) = self.search(&block.header, target, threads);
+            hashes += round_hashes;
+            let stats = MiningStats {
+                hashes,
+                elapsed: started.elapsed(),
+                threads,
+            };
+
+            if let Some(nonce) = nonce {
+                block.header.nonce = nonce;
+                block.hash = block.header.hash_with(self.block_hash);
+                return Ok(stats);
+            }
+            if self.cancel.is_cancelled() {
+                return Err(MiningErr::Cancelled(stats));
+            }
+
+            on_event(&MiningEvent::NoncesExhausted(stats));
+            match self.roll(block) {
+                Some(event) => on_event(&event),
+                None => return Err(MiningErr::Exhausted(stats)),
+            }
+        }
+    }
+
+    fn roll(&self, block: &mut Block) -> Option<MiningEvent> {
+        let timestamp = (block.header.timestamp + 1).max(self.clock.now());
+        if self.max_timestamp.is_some_and(|max| timestamp <= max) {
+            block.header.timestamp = timestamp;
+            return Some(MiningEvent::TimestampRolled(timestamp));
+        }
+
+        let coinbase = block
+            .transactions
+            .first_mut()
+            .filter(|transaction| transaction.is_coinbase())?;
+        coinbase.extra_nonce = coinbase.extra_nonce.checked_add(1)?;
+        let extra_nonce = coinbase.extra_nonce;
+        block.header.merkle_root = block.merkle_root();
+
+        Some(MiningEvent::ExtraNonceRolled(extra_nonce))
+    }
+
+    fn search(&self, header: &BlockHeader, target: U256, threads: usize) -> (Option<u64>, u64) {
+        let hashes = AtomicU64::new(0);
+        let best = AtomicU64::new(u64::MAX);
+
+        thread::scope(|scope| {
+            for worker in 0..threads {
+                let mut header = header.clone();
+                let (hashes, best) = (&hashes, &best);
+                let nonces = self.nonces.start.saturating_add(worker as u64)..self.nonces.end;
+
+                scope.spawn(move || {
+                    let mut count = 0;
+
+                    for nonce in nonces.step_by(threads) {
+                        if best.load(Ordering::Relaxed) <= nonce || self.cancel.is_cancelled() {
+                            break;
+                        }
+
+                        header.nonce = nonce;
+                        count += 1;
+
+                        let hash = header.hash_with(self.pow_hash);
+                        if U256::from(&hash) <= target {
+                            best.fetch_min(nonce, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+
+                    hashes.fetch_add(count, Ordering::Relaxed);
+                });
+            }
+        });
+
+        let nonce = Some(best.into_inner()).filter(|&nonce| nonce != u64::MAX);
+
+        (nonce, hashes.into_inner())
+    }
+}
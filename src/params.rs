@@ -0,0 +1,42 @@
+use super::hashable::HashAlgorithm;
+
+pub struct Params {
+    pub block_hash: HashAlgorithm,
+    pub pow_hash: HashAlgorithm,
+    pub initial_bits: u32,
+    pub difficulty_window: u32,
+    pub target_block_interval: u128,
+    pub median_time_span: usize,
+    pub max_future_drift: u128,
+    pub initial_reward: u64,
+    pub halving_interval: u32,
+    pub tail_emission: u64,
+    pub coinbase_maturity: u32,
+}
+
+impl Params {
+    pub fn block_subsidy(&self, height: u32) -> u64 {
+        let halvings = height.checked_div(self.halving_interval).unwrap_or(0);
+        let reward = self.initial_reward.checked_shr(halvings).unwrap_or(0);
+
+        reward.max(self.tail_emission)
+    }
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            block_hash: HashAlgorithm::Sha256,
+            pow_hash: HashAlgorithm::Sha256,
+            initial_bits: 0x2000_ffff,
+            difficulty_window: 10,
+            target_block_interval: 10_000,
+            median_time_span: 11,
+            max_future_drift: 120_000,
+            initial_reward: 100,
+            halving_interval: 210_000,
+            tail_emission: 0,
+            coinbase_maturity: 10,
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use {
+    super::{
+        block::Block,
+        encoding::{Decodable, Encodable},
+        types::Hash256,
+        utility::u32_bytes,
+    },
+    std::{
+        collections::HashMap,
+        fs::{File, OpenOptions},
+        io::{self, Read, Seek, SeekFrom, Write},
+        path::Path,
+    },
+};
+
+const RECORD_MAGIC: [u8; 4] = *b"SDMT";
+const REMOVAL_MAGIC: [u8; 4] = *b"SDMR";
+const TIP_MAGIC: [u8; 4] = *b"SDMP";
+const RECORD_HEADER_LEN: u64 = 8;
+const RECORD_CHECKSUM_LEN: u64 = 4;
+
+enum Record {
+    Block(Block),
+    Removal(Hash256),
+    Tip(Hash256),
+}
+
+pub struct BlockStore {
+    file: File,
+    len: u64,
+    by_hash: HashMap<Hash256, u64>,
+    by_height: HashMap<u32, Vec<u64>>,
+    tip: Option<(u64, Hash256)>,
+}
+
+impl BlockStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut store = BlockStore {
+            file,
+            len: 0,
+            by_hash: HashMap::new(),
+            by_height: HashMap::new(),
+            tip: None,
+        };
+        store.recover()?;
+
+        Ok(store)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        0 == self.len
+    }
+
+    pub fn offset_by_hash(&self, hash: &Hash256) -> Option<u64> {
+        self.by_hash.get(hash).copied()
+    }
+
+    pub fn offsets_by_height(&self, height: u32) -> &[u64] {
+        self.by_height.get(&height).map_or(&[], |offsets| offsets)
+    }
+
+    pub fn offsets(&self) -> Vec<u64> {
+        let mut offsets = self.by_hash.values().copied().collect::<Vec<u64>>();
+        offsets.sort_unstable();
+
+        offsets
+    }
+
+    pub fn tip(&self) -> Option<(u64, Hash256)> {
+        self.tip
+    }
+
+    pub fn block_by_hash(&mut self, hash: &Hash256) -> io::Result<Option<Block>> {
+        match self.offset_by_hash(hash) {
+            Some(offset) => self.read_at(offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn blocks(&mut self) -> io::Result<Vec<Block>> {
+        self.offsets()
+            .into_iter()
+            .map(|offset| self.read_at(offset))
+            .collect()
+    }
+
+    pub fn read_at(&mut self, offset: u64) -> io::Result<Block> {
+        match self.read_record(offset)? {
+            Some((Record::Block(block), _)) => Ok(block),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "corrupt block record",
+            )),
+        }
+    }
+
+    pub fn append(&mut self, block: &Block) -> io::Result<u64> {
+        let offset = self.write_record(RECORD_MAGIC, &block.to_bytes())?;
+        self.index(block, offset);
+
+        Ok(offset)
+    }
+
+    pub fn remove(&mut self, hash: &Hash256) -> io::Result<bool> {
+        if !self.by_hash.contains_key(hash) {
+            return Ok(false);
+        }
+
+        self.write_record(REMOVAL_MAGIC, hash.as_bytes())?;
+        self.unindex(hash);
+
+        Ok(true)
+    }
+
+    pub fn set_tip(&mut self, hash: &Hash256) -> io::Result<()> {
+        let offset = self.write_record(TIP_MAGIC, hash.as_bytes())?;
+        self.tip = Some((offset, *hash));
+
+        Ok(())
+    }
+
+    fn write_record(&mut self, magic: [u8; 4], payload: &[u8]) -> io::Result<u64> {
+        let mut record = vec![];
+
+        record.extend(&magic);
+        record.extend(&u32_bytes(&(payload.len() as u32)));
+        record.extend(payload);
+        record.extend(&checksum(payload));
+
+        let offset = self.len;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.len += record.len() as u64;
+
+        Ok(offset)
+    }
+
+    fn index(&mut self, block: &Block, offset: u64) {
+        self.by_hash.insert(block.hash, offset);
+        self.by_height
+            .entry(block.header.index)
+            .or_default()
+            .push(offset);
+    }
+
+    fn unindex(&mut self, hash: &Hash256) {
+        let Some(offset) = self.by_hash.remove(hash) else {
+            return;
+        };
+
+        self.by_height.retain(|_, offsets| {
+            offsets.retain(|&other| other != offset);
+            !offsets.is_empty()
+        });
+    }
+
+    fn recover(&mut self) -> io::Result<()> {
+        let file_len = self.file.metadata()?.len();
+        let mut offset = 0;
+
+        while offset < file_len {
+            match self.read_record(offset)? {
+                Some((Record::Block(block), next)) => {
+                    self.index(&block, offset);
+                    offset = next;
+                }
+                Some((Record::Removal(hash), next)) => {
+                    self.unindex(&hash);
+                    offset = next;
+                }
+                Some((Record::Tip(hash), next)) => {
+                    self.tip = Some((offset, hash));
+                    offset = next;
+                }
+                None => break,
+            }
+        }
+
+        if offset < file_len {
+            self.file.set_len(offset)?;
+            self.file.sync_all()?;
+        }
+        self.len = offset;
+
+        Ok(())
+    }
+
+    fn read_record(&mut self, offset: u64) -> io::Result<Option<(Record, u64)>> {
+        let file_len = self.file.metadata()?.len();
+        if file_len < offset + RECORD_HEADER_LEN {
+            return Ok(None);
+        }
+
+        let mut header = [0; RECORD_HEADER_LEN as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut header)?;
+
+        let payload_len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        let next = offset + RECORD_HEADER_LEN + payload_len + RECORD_CHECKSUM_LEN;
+        let magic = [header[0], header[1], header[2], header[3]];
+        if ![RECORD_MAGIC, REMOVAL_MAGIC, TIP_MAGIC].contains(&magic) || file_len < next {
+            return Ok(None);
+        }
+
+        let mut payload = vec![0; payload_len as usize];
+        let mut stored_checksum = [0; RECORD_CHECKSUM_LEN as usize];
+        self.file.read_exact(&mut payload)?;
+        self.file.read_exact(&mut stored_checksum)?;
+
+        if stored_checksum != checksum(&payload) {
+            return Ok(None);
+        }
+
+        let record = match magic {
+            RECORD_MAGIC => Block::from_bytes(&payload).ok().map(Record::Block),
+            REMOVAL_MAGIC => Hash256::from_slice(&payload).map(Record::Removal),
+            _ => Hash256::from_slice(&payload).map(Record::Tip),
+        };
+
+        Ok(record.map(|record| (record, next)))
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = crypto_hash::digest(crypto_hash::Algorithm::SHA256, payload);
+
+    [digest[0], digest[1], digest[2], digest[3]]
+}
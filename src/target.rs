@@ -0,0 +1,328 @@
+use {
+    super::types::Hash256,
+    std::{
+        cmp::Ordering,
+        fmt::{self, Debug, Display, Formatter, LowerHex},
+        ops::{Div, Not, Shl, Shr},
+    },
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct U256(pub [u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    pub fn from_le_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0; 4];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+            *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        U256(limbs)
+    }
+
+    pub fn to_le_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_compact(bits: u32) -> Option<Self> {
+        let size = bits >> 24;
+        let word = bits & 0x007f_ffff;
+
+        if 0 != word && 0 != bits & 0x0080_0000 {
+            return None;
+        }
+        if 0 != word && (34 < size || (0xff < word && 33 < size) || (0xffff < word && 32 < size)) {
+            return None;
+        }
+
+        Some(if size <= 3 {
+            U256::from(word as u64 >> (8 * (3 - size)))
+        } else {
+            U256::from(word as u64) << (8 * (size - 3))
+        })
+    }
+
+    pub fn to_compact(&self) -> u32 {
+        let mut size = self.bits().div_ceil(8);
+        let mut compact = if size <= 3 {
+            (self.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            (*self >> (8 * (size - 3))).low_u64() as u32
+        };
+
+        if 0 != compact & 0x0080_0000 {
+            compact >>= 8;
+            size += 1;
+        }
+
+        compact | (size << 24)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self == &U256::ZERO
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+            .iter()
+            .rposition(|&limb| 0 != limb)
+            .map_or(0, |i| 64 * i as u32 + 64 - self.0[i].leading_zeros())
+    }
+
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    pub fn checked_add(self, other: U256) -> Option<U256> {
+        let mut limbs = [0; 4];
+        let mut carry = false;
+
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let (sum, overflow_a) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, overflow_b) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = overflow_a || overflow_b;
+        }
+
+        (!carry).then_some(U256(limbs))
+    }
+
+    pub fn saturating_add(self, other: U256) -> U256 {
+        self.checked_add(other).unwrap_or(U256::MAX)
+    }
+
+    pub fn checked_sub(self, other: U256) -> Option<U256> {
+        let (difference, borrow) = self.overflowing_sub(other);
+
+        (!borrow).then_some(difference)
+    }
+
+    pub fn checked_mul(self, other: U256) -> Option<U256> {
+        let mut limbs = [0; 4];
+
+        for i in 0..4 {
+            let mut carry = 0;
+
+            for j in 0..4 {
+                let product = self.0[i] as u128 * other.0[j] as u128 + carry;
+
+                if i + j < 4 {
+                    let product = product + limbs[i + j] as u128;
+                    limbs[i + j] = product as u64;
+                    carry = product >> 64;
+                } else if 0 != product {
+                    return None;
+                }
+            }
+
+            if 0 != carry {
+                return None;
+            }
+        }
+
+        Some(U256(limbs))
+    }
+
+    pub fn checked_div(self, divisor: U256) -> Option<U256> {
+        if divisor.is_zero() {
+            return None;
+        }
+
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+
+        for bit in (0..self.bits()).rev() {
+            let carry = 256 == remainder.bits();
+            remainder = remainder << 1;
+            remainder.0[0] |= self.0[bit as usize / 64] >> (bit % 64) & 1;
+
+            if carry || divisor <= remainder {
+                remainder = remainder.overflowing_sub(divisor).0;
+                quotient.0[bit as usize / 64] |= 1 << (bit % 64);
+            }
+        }
+
+        Some(quotient)
+    }
+
+    pub fn from_f64(value: f64) -> Self {
+        if value.is_nan() || value < 1.0 {
+            return U256::ZERO;
+        } else if 256.0 <= value.log2() {
+            return U256::MAX;
+        }
+
+        let exponent = value.log2().floor() as u32;
+        if exponent < 64 {
+            U256::from(value as u64)
+        } else {
+            U256::from((value / 2f64.powi(exponent as i32 - 63)) as u64) << (exponent - 63)
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.0.iter().rev().fold(0.0, |total, &limb| {
+            total * 18_446_744_073_709_551_616.0 + limb as f64
+        })
+    }
+
+    fn overflowing_sub(self, other: U256) -> (U256, bool) {
+        let mut limbs = [0; 4];
+        let mut borrow = false;
+
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let (difference, overflow_a) = self.0[i].overflowing_sub(other.0[i]);
+            let (difference, overflow_b) = difference.overflowing_sub(borrow as u64);
+            *limb = difference;
+            borrow = overflow_a || overflow_b;
+        }
+
+        (U256(limbs), borrow)
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        U256([value, 0, 0, 0])
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(value: u128) -> Self {
+        U256([value as u64, (value >> 64) as u64, 0, 0])
+    }
+}
+
+impl From<&Hash256> for U256 {
+    fn from(hash: &Hash256) -> Self {
+        U256::from_le_bytes(hash.0)
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Not for U256 {
+    type Output = U256;
+
+    fn not(self) -> U256 {
+        U256(self.0.map(|limb| !limb))
+    }
+}
+
+impl Shl<u32> for U256 {
+    type Output = U256;
+
+    fn shl(self, shift: u32) -> U256 {
+        let mut limbs = [0; 4];
+        let (words, bits) = (shift as usize / 64, shift % 64);
+
+        for (i, limb) in limbs.iter_mut().enumerate().skip(words) {
+            *limb = self.0[i - words] << bits;
+            if 0 < bits && words < i {
+                *limb |= self.0[i - words - 1] >> (64 - bits);
+            }
+        }
+
+        U256(limbs)
+    }
+}
+
+impl Shr<u32> for U256 {
+    type Output = U256;
+
+    fn shr(self, shift: u32) -> U256 {
+        let mut limbs = [0; 4];
+        let (words, bits) = (shift as usize / 64, shift % 64);
+
+        for (i, limb) in limbs
+            .iter_mut()
+            .enumerate()
+            .take(4usize.saturating_sub(words))
+        {
+            *limb = self.0[i + words] >> bits;
+            if 0 < bits && i + words + 1 < 4 {
+                *limb |= self.0[i + words + 1] << (64 - bits);
+            }
+        }
+
+        U256(limbs)
+    }
+}
+
+impl Div for U256 {
+    type Output = U256;
+
+    fn div(self, divisor: U256) -> U256 {
+        self.checked_div(divisor)
+            .expect("attempt to divide by zero")
+    }
+}
+
+impl LowerHex for U256 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut bytes = self.to_le_bytes();
+        bytes.reverse();
+        let hex = hex::encode(bytes);
+        let digits = hex.trim_start_matches('0');
+
+        f.pad_integral(true, "0x", if digits.is_empty() { "0" } else { digits })
+    }
+}
+
+impl Display for U256 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self)
+    }
+}
+
+impl Debug for U256 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "U256({:#x})", self)
+    }
+}
+
+pub fn target(bits: u32) -> Option<U256> {
+    U256::from_compact(bits).filter(|target| !target.is_zero())
+}
+
+pub fn block_work(bits: u32) -> U256 {
+    match target(bits) {
+        Some(target) => (!target / target.saturating_add(U256::ONE)).saturating_add(U256::ONE),
+        None => U256::ZERO,
+    }
+}
+
+pub fn difficulty(bits: u32, pow_limit: u32) -> f64 {
+    match (target(bits), target(pow_limit)) {
+        (Some(target), Some(pow_limit)) => pow_limit.as_f64() / target.as_f64(),
+        _ => 0.0,
+    }
+}
+
+pub fn bits_for_difficulty(difficulty: f64, pow_limit: u32) -> u32 {
+    match target(pow_limit) {
+        Some(pow_limit) if 1.0 < difficulty => U256::from_f64(pow_limit.as_f64() / difficulty)
+            .clamp(U256::ONE, pow_limit)
+            .to_compact(),
+        _ => pow_limit,
+    }
+}
@@ -0,0 +1,141 @@
+use {
+    super::{
+        block::{Block, MAX_BLOCK_SIZE},
+        blockchain::{check_transaction, Blockchain},
+        encoding::Encodable,
+        mempool::Mempool,
+        miner::{Miner, MiningErr},
+        transaction::{money_range, OutPoint, Output, Transaction},
+        types::{Address, Hash256},
+    },
+    std::collections::HashSet,
+};
+
+pub trait TransactionSource {
+    fn transactions(&self) -> Vec<&Transaction>;
+}
+
+impl TransactionSource for Mempool {
+    fn transactions(&self) -> Vec<&Transaction> {
+        self.entries()
+            .into_iter()
+            .map(|entry| &entry.transaction)
+            .collect()
+    }
+}
+
+impl TransactionSource for [Transaction] {
+    fn transactions(&self) -> Vec<&Transaction> {
+        self.iter().collect()
+    }
+}
+
+impl TransactionSource for Vec<Transaction> {
+    fn transactions(&self) -> Vec<&Transaction> {
+        self.iter().collect()
+    }
+}
+
+pub struct BlockTemplate {
+    pub to_addr: Address,
+    pub max_size: usize,
+}
+
+impl BlockTemplate {
+    pub fn new(to_addr: Address) -> Self {
+        BlockTemplate {
+            to_addr,
+            max_size: MAX_BLOCK_SIZE - 1_000,
+        }
+    }
+
+    pub fn build<S>(&self, blockchain: &Blockchain, source: &S) -> Block
+    where
+        S: TransactionSource + ?Sized,
+    {
+        let index = blockchain.blocks.len() as u32;
+        let (prev_block_hash, timestamp) = match blockchain.blocks.last() {
+            Some(tip) => (
+                tip.hash,
+                blockchain.now().max(blockchain.median_time_past() + 1),
+            ),
+            None => (Hash256::ZERO, blockchain.now()),
+        };
+
+        let mut candidates = source
+            .transactions()
+            .into_iter()
+            .filter(|transaction| !transaction.is_coinbase())
+            .filter_map(|transaction| {
+                let fee = check_transaction(
+                    0,
+                    transaction,
+                    index,
+                    blockchain.params().coinbase_maturity,
+                    |outpoint| blockchain.unspent_output(outpoint),
+                )
+                .ok()?;
+
+                Some((transaction, fee, transaction.to_bytes().len()))
+            })
+            .collect::<Vec<(&Transaction, u64, usize)>>();
+        candidates.sort_by(|(_, fee_a, size_a), (_, fee_b, size_b)| {
+            (*fee_b as u128 * *size_a as u128).cmp(&(*fee_a as u128 * *size_b as u128))
+        });
+
+        let mut coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![Output {
+                to_addr: self.to_addr.clone(),
+                value: blockchain.params().block_subsidy(index),
+            }],
+            height: index,
+            extra_nonce: 0,
+        };
+        let mut size = coinbase.to_bytes().len();
+        let mut spent: HashSet<&OutPoint> = HashSet::new();
+        let mut transactions = vec![];
+        let mut fees: u64 = 0;
+
+        for (transaction, fee, tx_size) in candidates {
+            let Some(total_fee) = fees.checked_add(fee).filter(|&fees| money_range(fees)) else {
+                continue;
+            };
+
+            if self.max_size < size + tx_size
+                || transaction
+                    .inputs
+                    .iter()
+                    .any(|input| spent.contains(&input.outpoint))
+            {
+                continue;
+            }
+
+            spent.extend(transaction.inputs.iter().map(|input| &input.outpoint));
+            size += tx_size;
+            fees = total_fee;
+            transactions.push(transaction.clone());
+        }
+
+        coinbase.outputs[0].value = coinbase.outputs[0].value.saturating_add(fees);
+        transactions.insert(0, coinbase);
+
+        Block::new(
+            index,
+            timestamp,
+            prev_block_hash,
+            transactions,
+            blockchain.next_bits(),
+        )
+    }
+
+    pub fn mine<S>(&self, blockchain: &Blockchain, source: &S) -> Result<Block, MiningErr>
+    where
+        S: TransactionSource + ?Sized,
+    {
+        let mut block = self.build(blockchain, source);
+        Miner::for_blockchain(blockchain).mine(&mut block)?;
+
+        Ok(block)
+    }
+}
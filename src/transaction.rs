@@ -1,80 +1,199 @@
-use {
-    super::{
-        hashable::Hashable,
-        types::{Address, Hash},
-        utility::u64_bytes,
+use super::{
+    encoding::{
+        encode_bytes, encode_list, encode_varint, Decodable, DecodeErr, Decoder, Encodable,
+        ENCODING_VERSION,
     },
-    std::collections::HashSet,
+    hashable::Hashable,
+    types::{Address, Hash256},
+    utility::{u32_bytes, u64_bytes},
 };
 
-#[derive(Clone)]
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+pub fn money_range(value: u64) -> bool {
+    value <= MAX_MONEY
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    pub txid: Hash256,
+    pub index: u32,
+}
+
+impl Encodable for OutPoint {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.txid.encode(bytes);
+        bytes.extend(&u32_bytes(&self.index));
+    }
+}
+
+impl Decodable for OutPoint {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        Ok(OutPoint {
+            txid: Hash256::decode(decoder)?,
+            index: decoder.u32()?,
+        })
+    }
+}
+
+impl Hashable for OutPoint {
+    fn bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+    pub outpoint: OutPoint,
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Input {
+    pub fn new(outpoint: OutPoint) -> Self {
+        Input {
+            outpoint,
+            public_key: vec![],
+            signature: vec![],
+        }
+    }
+}
+
+impl Encodable for Input {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.outpoint.encode(bytes);
+        encode_bytes(bytes, &self.public_key);
+        encode_bytes(bytes, &self.signature);
+    }
+}
+
+impl Decodable for Input {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        Ok(Input {
+            outpoint: OutPoint::decode(decoder)?,
+            public_key: decoder.bytes()?,
+            signature: decoder.bytes()?,
+        })
+    }
+}
+
+impl Hashable for Input {
+    fn bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Output {
     pub to_addr: Address,
     pub value: u64,
 }
 
-impl Hashable for Output {
-    fn bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
-
-        bytes.extend(self.to_addr.as_bytes());
+impl Encodable for Output {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        encode_bytes(bytes, self.to_addr.as_bytes());
         bytes.extend(&u64_bytes(&self.value));
+    }
+}
 
-        bytes
+impl Decodable for Output {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        Ok(Output {
+            to_addr: decoder.string()?,
+            value: decoder.u64()?,
+        })
     }
 }
 
+impl Hashable for Output {
+    fn bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Transaction {
-    pub inputs: Vec<Output>,
+    pub inputs: Vec<Input>,
     pub outputs: Vec<Output>,
+    pub height: u32,
+    pub extra_nonce: u64,
 }
 
 impl Transaction {
-    pub fn input_value(&self) -> u64 {
-        self.inputs.iter().map(|input| input.value).sum()
-    }
+    pub fn output_value(&self) -> Option<u64> {
+        self.outputs.iter().try_fold(0u64, |total, output| {
+            if !money_range(output.value) {
+                return None;
+            }
 
-    pub fn output_value(&self) -> u64 {
-        self.outputs.iter().map(|output| output.value).sum()
+            total
+                .checked_add(output.value)
+                .filter(|&total| money_range(total))
+        })
     }
 
-    pub fn input_hashes(&self) -> HashSet<Hash> {
-        self.inputs
-            .iter()
-            .map(|input| input.hash())
-            .collect::<HashSet<Hash>>()
-    }
+    pub fn outpoints(&self) -> Vec<(OutPoint, Output)> {
+        let txid = self.hash();
 
-    pub fn output_hashes(&self) -> HashSet<Hash> {
         self.outputs
             .iter()
-            .map(|output| output.hash())
-            .collect::<HashSet<Hash>>()
+            .enumerate()
+            .map(|(index, output)| {
+                (
+                    OutPoint {
+                        txid,
+                        index: index as u32,
+                    },
+                    output.clone(),
+                )
+            })
+            .collect()
     }
 
     pub fn is_coinbase(&self) -> bool {
-        self.inputs.len() == 0
+        self.inputs.is_empty()
+    }
+
+    pub fn sighash(&self) -> Hash256 {
+        let mut bytes = vec![ENCODING_VERSION];
+
+        encode_varint(&mut bytes, self.inputs.len() as u64);
+        for input in &self.inputs {
+            input.outpoint.encode(&mut bytes);
+        }
+        encode_list(&mut bytes, &self.outputs);
+        bytes.extend(&u32_bytes(&self.height));
+        bytes.extend(&u64_bytes(&self.extra_nonce));
+
+        Hash256::sha256(&bytes)
+    }
+}
+
+impl Encodable for Transaction {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.push(ENCODING_VERSION);
+        encode_list(bytes, &self.inputs);
+        encode_list(bytes, &self.outputs);
+        bytes.extend(&u32_bytes(&self.height));
+        bytes.extend(&u64_bytes(&self.extra_nonce));
+    }
+}
+
+impl Decodable for Transaction {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        decoder.version()?;
+
+        Ok(Transaction {
+            inputs: decoder.list()?,
+            outputs: decoder.list()?,
+            height: decoder.u32()?,
+            extra_nonce: decoder.u64()?,
+        })
     }
 }
 
 impl Hashable for Transaction {
     fn bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
-
-        bytes.extend(
-            self.inputs
-                .iter()
-                .flat_map(|input| input.bytes())
-                .collect::<Vec<u8>>(),
-        );
-
-        bytes.extend(
-            self.outputs
-                .iter()
-                .flat_map(|output| output.bytes())
-                .collect::<Vec<u8>>(),
-        );
-
-        bytes
+        self.to_bytes()
     }
 }
@@ -1,2 +1,100 @@
-pub type Hash = Vec<u8>;
+use {
+    super::encoding::{Decodable, DecodeErr, Decoder, Encodable},
+    std::{
+        array::TryFromSliceError,
+        fmt::{self, Debug, Display, Formatter},
+        str::FromStr,
+    },
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash256(pub [u8; 32]);
+
+impl Hash256 {
+    pub const LEN: usize = 32;
+    pub const ZERO: Hash256 = Hash256([0; 32]);
+
+    pub fn sha256(bytes: &[u8]) -> Self {
+        Hash256::from_slice(&crypto_hash::digest(crypto_hash::Algorithm::SHA256, bytes))
+            .expect("SHA-256 digest is 32 bytes")
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(Hash256)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self == &Hash256::ZERO
+    }
+}
+
+impl From<[u8; 32]> for Hash256 {
+    fn from(bytes: [u8; 32]) -> Self {
+        Hash256(bytes)
+    }
+}
+
+impl From<Hash256> for [u8; 32] {
+    fn from(hash: Hash256) -> Self {
+        hash.0
+    }
+}
+
+impl TryFrom<&[u8]> for Hash256 {
+    type Error = TryFromSliceError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        <[u8; 32]>::try_from(bytes).map(Hash256)
+    }
+}
+
+impl AsRef<[u8]> for Hash256 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Display for Hash256 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl Debug for Hash256 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Hash256({})", self)
+    }
+}
+
+impl FromStr for Hash256 {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(s, &mut bytes)?;
+
+        Ok(Hash256(bytes))
+    }
+}
+
+impl Encodable for Hash256 {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend(self.0);
+    }
+}
+
+impl Decodable for Hash256 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeErr> {
+        decoder.array().map(Hash256)
+    }
+}
+
 pub type Address = String;
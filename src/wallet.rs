@@ -0,0 +1,70 @@
+use {
+    super::{
+        transaction::Transaction,
+        types::{Address, Hash256},
+    },
+    ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey},
+};
+
+pub fn address(public_key: &[u8]) -> Address {
+    hex::encode(crypto_hash::digest(
+        crypto_hash::Algorithm::SHA256,
+        public_key,
+    ))
+}
+
+pub fn verify_signature(public_key: &[u8], sighash: &Hash256, signature: &[u8]) -> bool {
+    let Ok(public_key) = <[u8; 32]>::try_from(public_key) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return false;
+    };
+
+    VerifyingKey::from_bytes(&public_key)
+        .map(|key| key.verify(sighash.as_bytes(), &signature).is_ok())
+        .unwrap_or(false)
+}
+
+pub struct Wallet {
+    signing_key: SigningKey,
+}
+
+impl Wallet {
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let mut secret_key = [0; 32];
+        secret_key.copy_from_slice(&crypto_hash::digest(crypto_hash::Algorithm::SHA256, seed));
+
+        Wallet {
+            signing_key: SigningKey::from_bytes(&secret_key),
+        }
+    }
+
+    pub fn public_key(&self) -> Vec<u8> {
+        self.signing_key.verifying_key().to_bytes().to_vec()
+    }
+
+    pub fn address(&self) -> Address {
+        address(&self.public_key())
+    }
+
+    pub fn sign_input(&self, transaction: &mut Transaction, index: usize) {
+        let sighash = transaction.sighash();
+        let input = &mut transaction.inputs[index];
+
+        input.public_key = self.public_key();
+        input.signature = self
+            .signing_key
+            .sign(sighash.as_bytes())
+            .to_bytes()
+            .to_vec();
+    }
+
+    pub fn sign(&self, mut transaction: Transaction) -> Transaction {
+        for index in 0..transaction.inputs.len() {
+            self.sign_input(&mut transaction, index);
+        }
+
+        transaction
+    }
+}